@@ -10,6 +10,9 @@ DEST="${DEST:-/usr/local/bin/jorik}"
 TOKEN="${GITHUB_TOKEN:-}"
 # If you want a specific tag, set TAG=vX.Y.Z (otherwise uses /releases/latest)
 TAG="${TAG:-}"
+# Set COMPLETIONS=0 to skip installing shell completions and man pages
+COMPLETIONS="${COMPLETIONS:-1}"
+PREFIX="${PREFIX:-$(dirname "$(dirname "$DEST")")}"
 
 API="${HOST}/repos/${OWNER}/${REPO}/releases"
 AUTH=()
@@ -58,5 +61,31 @@ else
   "${install_cmd[@]}"
 fi
 
+# Run a command with sudo if the directory it writes to isn't writable
+as_needed() {
+  local dir="$1"
+  shift
+  if [[ -d "$dir" && -w "$dir" ]] || [[ ! -e "$dir" && -w "$(dirname "$dir")" ]]; then
+    "$@"
+  else
+    sudo "$@"
+  fi
+}
+
+if [[ "$COMPLETIONS" == "1" ]]; then
+  echo "Installing shell completions and man pages under $PREFIX/share..."
+  "$DEST" completions bash >"$tmp/jorik.bash"
+  "$DEST" completions zsh >"$tmp/_jorik"
+  "$DEST" completions fish >"$tmp/jorik.fish"
+  "$DEST" man -o "$tmp/man" >/dev/null
+
+  share="$PREFIX/share"
+  as_needed "$share" install -D -m 0644 "$tmp/jorik.bash" "$share/bash-completion/completions/jorik"
+  as_needed "$share" install -D -m 0644 "$tmp/_jorik" "$share/zsh/site-functions/_jorik"
+  as_needed "$share" install -D -m 0644 "$tmp/jorik.fish" "$share/fish/vendor_completions.d/jorik.fish"
+  as_needed "$share" install -d "$share/man/man1"
+  as_needed "$share" install -m 0644 "$tmp"/man/*.1 "$share/man/man1/"
+fi
+
 echo "Done. Version:"
 "$DEST" --help >/dev/null 2>&1 && "$DEST" --version || true
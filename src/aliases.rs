@@ -0,0 +1,143 @@
+//! User-defined query aliases and multi-step macros.
+//!
+//! Aliases live in `settings.json` under `aliases`. A string value is a query
+//! alias (`jorik turip` plays it); a list is a macro whose steps are ordinary
+//! jorik command lines, run in order by `jorik run <name>`.
+
+use crate::api::Settings;
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Alias {
+    Query(String),
+    Macro(Vec<String>),
+}
+
+/// Macros may invoke other aliases; this bounds the nesting so a macro that
+/// (indirectly) calls itself fails instead of looping forever.
+pub const MAX_DEPTH: usize = 8;
+
+/// Aliases shipped with the CLI. User aliases with the same name win.
+pub fn defaults() -> BTreeMap<String, Alias> {
+    BTreeMap::from([(
+        "turip".to_string(),
+        Alias::Query("https://open.spotify.com/track/2RQWB4Asy1rjZL4IUcJ7kn".to_string()),
+    )])
+}
+
+/// Shipped defaults merged with the user's aliases.
+pub fn all(settings: &Settings) -> BTreeMap<String, Alias> {
+    let mut aliases = defaults();
+    aliases.extend(settings.aliases.clone());
+    aliases
+}
+
+/// The alias `name` invoked `depth` levels deep (0 from the command line).
+pub fn lookup<'a>(
+    aliases: &'a BTreeMap<String, Alias>,
+    name: &str,
+    depth: usize,
+) -> Result<&'a Alias> {
+    let Some(alias) = aliases.get(name) else {
+        bail!("unknown command or alias {:?} (see `jorik run` for aliases)", name);
+    };
+    if depth >= MAX_DEPTH {
+        bail!("alias {:?} nests too deeply (recursive macro?)", name);
+    }
+    Ok(alias)
+}
+
+/// Turn an alias into the argument lists (without the program name) of the
+/// commands to run. Extra arguments are only accepted by query aliases, where
+/// they are forwarded to `play`.
+pub fn expand(name: &str, alias: &Alias, extra: &[String]) -> Result<Vec<Vec<String>>> {
+    match alias {
+        Alias::Query(query) => {
+            let mut args = vec!["play".to_string(), query.clone()];
+            args.extend(extra.iter().cloned());
+            Ok(vec![args])
+        }
+        Alias::Macro(steps) => {
+            if !extra.is_empty() {
+                bail!("macro {:?} does not take arguments", name);
+            }
+            steps
+                .iter()
+                .map(|step| match shlex::split(step) {
+                    Some(args) if !args.is_empty() => Ok(args),
+                    _ => bail!("macro {:?} has an invalid step: {:?}", name, step),
+                })
+                .collect()
+        }
+    }
+}
+
+pub fn describe(alias: &Alias) -> String {
+    match alias {
+        Alias::Query(query) => query.clone(),
+        Alias::Macro(steps) => steps.join("; "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn args(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn expands_aliases() {
+        let query = Alias::Query("lofi beats".to_string());
+        assert_eq!(
+            expand("lofi", &query, &args(&["--shuffle"])).unwrap(),
+            [args(&["play", "lofi beats", "--shuffle"])]
+        );
+        let steps = Alias::Macro(args(&["stop --yes", "play 'lofi beats'", "filter nightcore"]));
+        assert_eq!(
+            expand("chill", &steps, &[]).unwrap(),
+            [
+                args(&["stop", "--yes"]),
+                args(&["play", "lofi beats"]),
+                args(&["filter", "nightcore"]),
+            ]
+        );
+        assert!(expand("chill", &steps, &args(&["extra"])).is_err());
+        for bad in ["", "play 'unterminated"] {
+            assert!(expand("bad", &Alias::Macro(args(&[bad])), &[]).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn user_aliases_override_defaults() {
+        let settings = Settings {
+            aliases: serde_json::from_value(json!({
+                "turip": "ytsearch:turip",
+                "chill": ["play lofi", "volume 50"],
+            }))
+            .unwrap(),
+            ..Default::default()
+        };
+        let aliases = all(&settings);
+        assert_eq!(aliases["turip"], Alias::Query("ytsearch:turip".to_string()));
+        assert_eq!(aliases["chill"], Alias::Macro(args(&["play lofi", "volume 50"])));
+        assert!(all(&Settings::default()).contains_key("turip"));
+    }
+
+    #[test]
+    fn bounds_recursive_macros() {
+        let aliases = BTreeMap::from([("again".to_string(), Alias::Macro(args(&["run again"])))]);
+        assert!(lookup(&aliases, "missing", 0).is_err());
+        // `again` runs itself, one level deeper each time
+        let mut depth = 0;
+        while lookup(&aliases, "again", depth).is_ok() {
+            depth += 1;
+        }
+        assert_eq!(depth, MAX_DEPTH);
+    }
+}
@@ -1,11 +1,16 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 pub use crate::GiteaAsset;
-use dirs::config_dir;
-use reqwest::Url;
+use crate::aliases::Alias;
+use crate::hooks::Hook;
+use crate::search::SearchSource;
+use dirs::{config_dir, data_dir};
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::OnceLock;
 
 #[derive(Serialize, Clone)]
 pub struct PlayPayload {
@@ -16,6 +21,18 @@ pub struct PlayPayload {
     pub user_id: Option<String>,
     pub requested_by: Option<String>,
     pub avatar_url: Option<String>,
+    /// Queue position to insert at (0 = next up); appended when omitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<usize>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SearchPayload {
+    pub action: &'static str,
+    pub guild_id: Option<String>,
+    pub user_id: Option<String>,
+    pub query: String,
+    pub limit: usize,
 }
 
 #[derive(Serialize, Clone)]
@@ -34,6 +51,14 @@ pub struct QueuePayload {
     pub offset: usize,
 }
 
+#[derive(Serialize, Clone)]
+pub struct SeekPayload {
+    pub action: &'static str,
+    pub guild_id: Option<String>,
+    pub user_id: Option<String>,
+    pub position_ms: u64,
+}
+
 #[derive(Serialize, Clone)]
 pub struct LoopPayload {
     pub action: &'static str,
@@ -65,7 +90,7 @@ pub struct LyricsPayload {
     pub user_id: Option<String>,
 }
 
-#[derive(Serialize, Default, Clone)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
 pub struct AudioFilters {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub volume: Option<f32>,
@@ -89,80 +114,91 @@ pub struct AudioFilters {
     pub low_pass: Option<LowPassOptions>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct EqualizerBand {
     pub band: i32,
     pub gain: f32,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct KaraokeOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub level: Option<f32>,
-    #[serde(rename = "monoLevel")]
+    #[serde(rename = "monoLevel", skip_serializing_if = "Option::is_none")]
     pub mono_level: Option<f32>,
-    #[serde(rename = "filterBand")]
+    #[serde(rename = "filterBand", skip_serializing_if = "Option::is_none")]
     pub filter_band: Option<f32>,
-    #[serde(rename = "filterWidth")]
+    #[serde(rename = "filterWidth", skip_serializing_if = "Option::is_none")]
     pub filter_width: Option<f32>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TimescaleOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub speed: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub pitch: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub rate: Option<f32>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TremoloOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub frequency: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub depth: Option<f32>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct VibratoOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub frequency: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub depth: Option<f32>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct RotationOptions {
-    #[serde(rename = "rotationHz")]
+    #[serde(rename = "rotationHz", skip_serializing_if = "Option::is_none")]
     pub rotation_hz: Option<f32>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct DistortionOptions {
-    #[serde(rename = "sinOffset")]
+    #[serde(rename = "sinOffset", skip_serializing_if = "Option::is_none")]
     pub sin_offset: Option<f32>,
-    #[serde(rename = "sinScale")]
+    #[serde(rename = "sinScale", skip_serializing_if = "Option::is_none")]
     pub sin_scale: Option<f32>,
-    #[serde(rename = "cosOffset")]
+    #[serde(rename = "cosOffset", skip_serializing_if = "Option::is_none")]
     pub cos_offset: Option<f32>,
-    #[serde(rename = "cosScale")]
+    #[serde(rename = "cosScale", skip_serializing_if = "Option::is_none")]
     pub cos_scale: Option<f32>,
-    #[serde(rename = "tanOffset")]
+    #[serde(rename = "tanOffset", skip_serializing_if = "Option::is_none")]
     pub tan_offset: Option<f32>,
-    #[serde(rename = "tanScale")]
+    #[serde(rename = "tanScale", skip_serializing_if = "Option::is_none")]
     pub tan_scale: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub offset: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub scale: Option<f32>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ChannelMixOptions {
-    #[serde(rename = "leftToLeft")]
+    #[serde(rename = "leftToLeft", skip_serializing_if = "Option::is_none")]
     pub left_to_left: Option<f32>,
-    #[serde(rename = "leftToRight")]
+    #[serde(rename = "leftToRight", skip_serializing_if = "Option::is_none")]
     pub left_to_right: Option<f32>,
-    #[serde(rename = "rightToLeft")]
+    #[serde(rename = "rightToLeft", skip_serializing_if = "Option::is_none")]
     pub right_to_left: Option<f32>,
-    #[serde(rename = "rightToRight")]
+    #[serde(rename = "rightToRight", skip_serializing_if = "Option::is_none")]
     pub right_to_right: Option<f32>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct LowPassOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub smoothing: Option<f32>,
 }
 
@@ -216,6 +252,7 @@ pub struct WsAction<T> {
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Settings {
+    #[serde(default = "default_base_url")]
     pub base_url: String,
     #[serde(default = "default_offset")]
     pub visualizer_offset: i64,
@@ -225,12 +262,30 @@ pub struct Settings {
     pub visualizer_style: String,
     #[serde(default = "default_layout")]
     pub layout: String,
-}
-
+    #[serde(default = "default_true")]
+    pub show_stats_tab: bool,
+    /// `stop`/`clear` ask for confirmation when more tracks than this are queued
+    #[serde(default = "default_confirm_threshold")]
+    pub confirm_threshold: usize,
+    /// Query aliases and macros, see `aliases.rs`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, Alias>,
+    /// Commands run on WebSocket events, see `hooks.rs`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<Hook>,
+    /// How many hook commands may run at the same time
+    #[serde(default = "default_hook_concurrency")]
+    pub hook_concurrency: usize,
+}
+
+fn default_base_url() -> String { "https://jorik.xserv.pp.ua".to_string() }
 fn default_offset() -> i64 { 0 }
 fn default_theme() -> String { "Default".to_string() }
 fn default_viz() -> String { "Bars".to_string() }
 fn default_layout() -> String { "Standard".to_string() }
+fn default_true() -> bool { true }
+fn default_confirm_threshold() -> usize { 5 }
+fn default_hook_concurrency() -> usize { 4 }
 
 pub fn config_file_path() -> Option<PathBuf> {
     config_dir().map(|p| p.join("jorik-cli").join("auth.json"))
@@ -240,21 +295,89 @@ pub fn settings_file_path() -> Option<PathBuf> {
     config_dir().map(|p| p.join("jorik-cli").join("settings.json"))
 }
 
+pub fn presets_dir_path() -> Option<PathBuf> {
+    config_dir().map(|p| p.join("jorik-cli").join("presets"))
+}
+
+/// Directory holding per-profile data such as favorites.
+pub fn profiles_dir_path() -> Option<PathBuf> {
+    config_dir().map(|p| p.join("jorik-cli").join("profiles"))
+}
+
+pub fn profile_dir_path(profile: &str) -> Option<PathBuf> {
+    profiles_dir_path().map(|p| p.join(profile))
+}
+
+/// Profile names end up in paths, so keep them to a safe character set.
+pub fn validate_profile_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        bail!("invalid profile name {:?}: use letters, digits, '-', '_' or '.'", name);
+    }
+    Ok(())
+}
+
+pub fn history_file_path() -> Option<PathBuf> {
+    data_dir().map(|p| p.join("jorik-cli").join("history.jsonl"))
+}
+
+/// Command history of `jorik shell`.
+pub fn shell_history_file_path() -> Option<PathBuf> {
+    data_dir().map(|p| p.join("jorik-cli").join("shell_history"))
+}
+
+/// Control socket of `jorik daemon`, in the runtime directory where there is
+/// one.
+pub fn daemon_socket_path() -> Option<PathBuf> {
+    dirs::runtime_dir()
+        .or_else(data_dir)
+        .map(|p| p.join("jorik-cli").join("daemon.sock"))
+}
+
+/// Queue snapshot taken before the last destructive action, for `undo`.
+pub fn undo_file_path() -> Option<PathBuf> {
+    data_dir().map(|p| p.join("jorik-cli").join("undo.json"))
+}
+
+/// The saved settings, or the defaults when there is no settings file. A file
+/// that doesn't parse is an error rather than quietly ignored.
+pub fn try_load_settings() -> Result<Settings> {
+    let Some(path) = settings_file_path() else {
+        return Ok(Settings::default());
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Settings::default()),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+    };
+    serde_json::from_str(&contents).with_context(|| format!("parsing {}", path.display()))
+}
+
+/// [`try_load_settings`] where there is nowhere to report an error (shell
+/// completion), falling back to the defaults.
 pub fn load_settings() -> Settings {
-    if let Some(path) = settings_file_path() {
-        if let Ok(contents) = fs::read_to_string(&path) {
-            if let Ok(settings) = serde_json::from_str::<Settings>(&contents) {
-                return settings;
-            }
+    try_load_settings().unwrap_or_default()
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            base_url: default_base_url(),
+            visualizer_offset: 0,
+            theme: "Default".to_string(),
+            visualizer_style: "Bars".to_string(),
+            layout: "Standard".to_string(),
+            show_stats_tab: true,
+            confirm_threshold: default_confirm_threshold(),
+            aliases: BTreeMap::new(),
+            hooks: Vec::new(),
+            hook_concurrency: default_hook_concurrency(),
         }
     }
-    Settings {
-        base_url: "https://jorik.xserv.pp.ua".to_string(),
-        visualizer_offset: 0,
-        theme: "Default".to_string(),
-        visualizer_style: "Bars".to_string(),
-        layout: "Standard".to_string(),
-    }
 }
 
 pub fn save_settings(settings: &Settings) -> Result<()> {
@@ -267,6 +390,36 @@ pub fn save_settings(settings: &Settings) -> Result<()> {
     Ok(())
 }
 
+/// Settings that differ between profiles, stored in
+/// `profiles/<name>/settings.json`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ProfileSettings {
+    /// Search source used for plain-text queries without a `yt:`-style prefix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_source: Option<SearchSource>,
+}
+
+fn profile_settings_path(profile: &str) -> Option<PathBuf> {
+    profile_dir_path(profile).map(|p| p.join("settings.json"))
+}
+
+pub fn load_profile_settings(profile: &str) -> ProfileSettings {
+    profile_settings_path(profile)
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_profile_settings(profile: &str, settings: &ProfileSettings) -> Result<()> {
+    let path = profile_settings_path(profile).context("cannot determine profile path")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context("creating profile directory")?;
+    }
+    let json = serde_json::to_string_pretty(settings).context("serializing profile settings")?;
+    fs::write(&path, json).context("writing profile settings")?;
+    Ok(())
+}
+
 pub fn save_token(token: &str, avatar_url: Option<&str>, username: Option<&str>) -> Result<()> {
     let path = config_file_path().context("cannot determine config path")?;
     if let Some(parent) = path.parent() {
@@ -303,28 +456,178 @@ pub fn build_url(base: &str, path: &str) -> String {
     format!("{}{}", base.trim_end_matches('/'), path)
 }
 
-pub fn clean_query(input: &str) -> String {
-    if let Ok(mut url) = Url::parse(input) {
-        if url.cannot_be_a_base() || url.query().is_none() {
-            return input.to_string();
-        }
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum DryRun {
+    /// Print the URL and pretty-printed JSON body
+    Json,
+    /// Print an equivalent `curl` command line
+    Curl,
+}
+
+static DRY_RUN: OnceLock<DryRun> = OnceLock::new();
+
+/// Print requests instead of sending them for the rest of the process.
+pub fn set_dry_run(mode: DryRun) {
+    let _ = DRY_RUN.set(mode);
+}
+
+pub fn dry_run() -> Option<DryRun> {
+    DRY_RUN.get().copied()
+}
+
+/// Print a request that `--dry-run` kept from being sent.
+pub fn print_dry_run<T: Serialize>(url: &str, token: Option<&str>, body: &T) {
+    println!("{}", render_dry_run(dry_run().unwrap_or(DryRun::Json), url, token, body));
+}
 
-        let pairs: Vec<(String, String)> = url
-            .query_pairs()
-            .filter(|(k, _)| k != "si")
-            .map(|(k, v)| (k.into_owned(), v.into_owned()))
-            .collect();
-
-        if pairs.is_empty() {
-            url.set_query(None);
-        } else {
-            let mut serializer = url.query_pairs_mut();
-            serializer.clear();
-            for (k, v) in pairs {
-                serializer.append_pair(&k, &v);
+/// The request as `mode` shows it. The token itself is never included; the
+/// curl line reads it from `$JORIK_TOKEN`.
+fn render_dry_run<T: Serialize>(mode: DryRun, url: &str, token: Option<&str>, body: &T) -> String {
+    let json = serde_json::to_string(body).unwrap_or_default();
+    match mode {
+        DryRun::Json => {
+            let mut out = format!("POST {}\n", url);
+            if token.is_some() {
+                out.push_str("Authorization: Bearer <token>\n");
             }
+            out.push_str(&serde_json::to_string_pretty(body).unwrap_or(json));
+            out
+        }
+        DryRun::Curl => {
+            let quote = |s: &str| shlex::try_quote(s).map(|q| q.into_owned()).unwrap_or_default();
+            let mut line = format!(
+                "curl -X POST {} -H 'Content-Type: application/json'",
+                quote(url)
+            );
+            if token.is_some() {
+                line.push_str(" -H \"Authorization: Bearer $JORIK_TOKEN\"");
+            }
+            line.push_str(&format!(" -d {}", quote(&json)));
+            line
+        }
+    }
+}
+
+/// POST an action to the audio webhook and return the parsed response.
+/// Goes through `jorik daemon` when one is running.
+///
+/// With `--dry-run` the request is printed instead and a placeholder
+/// `{"dry_run": true}` response is returned.
+pub async fn fetch_audio<T: serde::Serialize>(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    payload: &T,
+) -> Result<Value> {
+    let url = build_url(base_url, "/webhook/audio");
+    if dry_run().is_some() {
+        print_dry_run(&url, token, payload);
+        return Ok(serde_json::json!({ "dry_run": true }));
+    }
+    let (status, text) = match crate::daemon::request(base_url, token, payload).await? {
+        Some(reply) => (reply.status(), reply.body),
+        None => {
+            let resp = send_audio(client, base_url, token, payload).await?;
+            let status = resp.status();
+            (status, resp.text().await.context("reading response body")?)
+        }
+    };
+    let json = parse_response(status, &text, &url)?;
+    crate::history::record_response(&json);
+    Ok(json)
+}
+
+/// POST an action to the audio webhook as is: no dry run, daemon or history.
+pub async fn send_audio<T: serde::Serialize>(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    payload: &T,
+) -> Result<reqwest::Response> {
+    let url = build_url(base_url, "/webhook/audio");
+    let mut req = client.post(&url).json(payload);
+    if let Some(bearer) = token {
+        req = req.bearer_auth(bearer);
+    }
+    req.send().await.with_context(|| format!("POST {url}"))
+}
+
+/// Parse a webhook response, turning non-2xx statuses and `{"error": ...}`
+/// bodies into errors.
+pub async fn read_response(resp: reqwest::Response, url: &str) -> Result<Value> {
+    let status = resp.status();
+    let text = resp.text().await.context("reading response body")?;
+    parse_response(status, &text, url)
+}
+
+/// [`read_response`] for a body that has already been read.
+pub fn parse_response(status: StatusCode, text: &str, url: &str) -> Result<Value> {
+    let json = serde_json::from_str::<Value>(text).ok();
+
+    let error = json.as_ref().and_then(|j| j.get("error"));
+    if !status.is_success() || error.is_some() {
+        let msg = json
+            .as_ref()
+            .and_then(|j| j.get("message").or(error))
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| text.to_string());
+        bail!("request failed ({}): {}", status, msg);
+    }
+    json.with_context(|| format!("invalid JSON response from {url}"))
+}
+
+/// Normalise pasted links (see `normalize.rs`) unless disabled with
+/// `--no-normalize`.
+pub fn clean_query(input: &str) -> String {
+    if crate::normalize::enabled() {
+        crate::normalize::normalize(input)
+    } else {
+        input.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn renders_dry_runs_without_the_token() {
+        let body = json!({"action": "play", "query": "don't stop"});
+        let url = "https://jorik.example/webhook/audio";
+        let cases = [
+            (
+                DryRun::Json,
+                Some("secret"),
+                concat!(
+                    "POST https://jorik.example/webhook/audio\n",
+                    "Authorization: Bearer <token>\n",
+                    "{\n  \"action\": \"play\",\n  \"query\": \"don't stop\"\n}",
+                ),
+            ),
+            (
+                DryRun::Curl,
+                Some("secret"),
+                concat!(
+                    "curl -X POST https://jorik.example/webhook/audio",
+                    " -H 'Content-Type: application/json'",
+                    " -H \"Authorization: Bearer $JORIK_TOKEN\"",
+                    " -d \"{\\\"action\\\":\\\"play\\\",\\\"query\\\":\\\"don't stop\\\"}\"",
+                ),
+            ),
+            (
+                DryRun::Curl,
+                None,
+                concat!(
+                    "curl -X POST https://jorik.example/webhook/audio",
+                    " -H 'Content-Type: application/json'",
+                    " -d \"{\\\"action\\\":\\\"play\\\",\\\"query\\\":\\\"don't stop\\\"}\"",
+                ),
+            ),
+        ];
+        for (mode, token, expected) in cases {
+            assert_eq!(render_dry_run(mode, url, token, &body), expected, "{:?}", mode);
         }
-        return url.to_string();
     }
-    input.to_string()
 }
@@ -0,0 +1,166 @@
+//! Values for shell completion that come from local data rather than from
+//! the clap definition: presets, profiles, favorites, aliases and the guild
+//! IDs seen in the history.
+//!
+//! [`annotate`] attaches them to a `clap::Command` for `jorik completions`;
+//! `jorik shell` asks [`source_for`] directly.
+
+use crate::aliases;
+use crate::api::{self, profiles_dir_path};
+use crate::favorites;
+use crate::filters;
+use crate::history;
+use clap::{Command, ValueEnum};
+use clap_complete::engine::{ArgValueCandidates, CompletionCandidate};
+use clap_complete::env::{Bash, Elvish, EnvCompleter, Fish, Powershell, Zsh};
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+    Elvish,
+}
+
+impl Shell {
+    pub fn completer(self) -> &'static dyn EnvCompleter {
+        match self {
+            Shell::Bash => &Bash,
+            Shell::Zsh => &Zsh,
+            Shell::Fish => &Fish,
+            Shell::Powershell => &Powershell,
+            Shell::Elvish => &Elvish,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    Presets,
+    Profiles,
+    /// Favorite names and tags
+    Favorites,
+    /// Favorite names only
+    FavoriteNames,
+    Aliases,
+    Guilds,
+}
+
+/// The source for argument `id` of the subcommand at `path` (space-separated,
+/// empty for the top level).
+pub fn source_for(path: &str, id: &str) -> Option<Source> {
+    match (path, id) {
+        (_, "guild_id" | "guild") => Some(Source::Guilds),
+        (_, "profile") => Some(Source::Profiles),
+        ("run", "name") => Some(Source::Aliases),
+        ("filter", "style")
+        | ("filter add", "preset")
+        | ("filter preset show" | "filter preset delete", "name") => Some(Source::Presets),
+        ("fav play", "name_or_tag") | ("fav list", "tag") => Some(Source::Favorites),
+        ("fav rm", "name") => Some(Source::FavoriteNames),
+        _ => None,
+    }
+}
+
+impl Source {
+    pub fn values(self, profile: &str) -> Vec<String> {
+        match self {
+            Source::Presets => filters::load_presets().0.into_iter().map(|p| p.name).collect(),
+            Source::Profiles => profiles(),
+            Source::Favorites => {
+                let favorites = favorites::load(profile);
+                let mut out: Vec<String> = favorites.iter().map(|f| f.name.clone()).collect();
+                for tag in favorites.iter().flat_map(|f| f.tags.iter()) {
+                    if !out.contains(tag) {
+                        out.push(tag.clone());
+                    }
+                }
+                out
+            }
+            Source::FavoriteNames => favorites::load(profile).into_iter().map(|f| f.name).collect(),
+            Source::Aliases => aliases::all(&api::load_settings()).into_keys().collect(),
+            Source::Guilds => history::guild_ids(),
+        }
+    }
+}
+
+/// `default` plus every profile with a directory.
+fn profiles() -> Vec<String> {
+    let mut names = vec!["default".to_string()];
+    if let Some(entries) = profiles_dir_path().and_then(|d| std::fs::read_dir(d).ok()) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.path().is_dir() && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Attach the dynamic values to every matching argument of `cmd` and its
+/// subcommands.
+pub fn annotate(cmd: Command) -> Command {
+    annotate_at(cmd, "")
+}
+
+fn annotate_at(mut cmd: Command, path: &str) -> Command {
+    // `mut_args` rather than `mut_arg`, which would reorder the positionals
+    cmd = cmd.mut_args(|arg| match source_for(path, arg.get_id().as_str()) {
+        Some(source) => arg.add(ArgValueCandidates::new(move || {
+            // `--profile` on the line being completed isn't known here
+            let profile = std::env::var("JORIK_PROFILE").unwrap_or_else(|_| "default".to_string());
+            source
+                .values(&profile)
+                .into_iter()
+                .map(CompletionCandidate::new)
+                .collect()
+        })),
+        None => arg,
+    });
+    let names: Vec<String> = cmd.get_subcommands().map(|s| s.get_name().to_string()).collect();
+    for name in names {
+        let sub_path = if path.is_empty() {
+            name.clone()
+        } else {
+            format!("{} {}", path, name)
+        };
+        cmd = cmd.mut_subcommand(name, |sub| annotate_at(sub, &sub_path));
+    }
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_name_real_arguments() {
+        let cases = [
+            ("run", "name", Source::Aliases),
+            ("filter", "style", Source::Presets),
+            ("filter add", "preset", Source::Presets),
+            ("filter preset show", "name", Source::Presets),
+            ("filter preset delete", "name", Source::Presets),
+            ("fav play", "name_or_tag", Source::Favorites),
+            ("fav list", "tag", Source::Favorites),
+            ("fav rm", "name", Source::FavoriteNames),
+            ("queue", "guild_id", Source::Guilds),
+            ("", "profile", Source::Profiles),
+        ];
+        let root = crate::cli_command();
+        for (path, id, source) in cases {
+            assert_eq!(source_for(path, id), Some(source), "{} {}", path, id);
+            // Renaming an argument would silently drop its completions
+            let mut cmd = &root;
+            for name in path.split_whitespace() {
+                cmd = cmd
+                    .find_subcommand(name)
+                    .unwrap_or_else(|| panic!("no subcommand {:?}", path));
+            }
+            assert!(cmd.get_arguments().any(|a| a.get_id() == id), "{} has no {}", path, id);
+        }
+        assert_eq!(source_for("play", "query"), None);
+    }
+}
@@ -0,0 +1,804 @@
+//! `jorik daemon`: a long-running process holding the WebSocket subscription
+//! and the last `nowplaying`/`queue` answers, so other commands don't start
+//! from scratch. It listens on a Unix socket ([`api::daemon_socket_path`]);
+//! `fetch_audio` and `post_audio` go through [`request`], which falls back to
+//! plain REST whenever there is no daemon or it serves another server or
+//! token.
+//!
+//! The protocol is one JSON object per line each way.
+//!
+//! While the guild's WebSocket is up, `nowplaying` and `queue` are answered
+//! from the cache (dropped whenever an event says it is out of date) and
+//! other actions are sent over the WebSocket like the TUI does. Everything
+//! else is forwarded over REST on the daemon's own client.
+//!
+//! With hooks configured (or `--guild-id`) the daemon subscribes to the guild
+//! right away and runs the [`hooks`] for its events.
+
+#![cfg_attr(not(unix), allow(dead_code))]
+
+use crate::api::{self, PlaybackState, WsAction};
+use crate::follow;
+use crate::hooks;
+use crate::ws::{self, Update};
+use anyhow::{Context, Result, bail};
+use colored::Colorize;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::{Mutex, Notify, oneshot};
+
+#[cfg(unix)]
+type Stream = tokio::net::UnixStream;
+/// There is no daemon without Unix sockets, so this is never connected.
+#[cfg(not(unix))]
+type Stream = tokio::io::DuplexStream;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Talk to the server directly for this process (`--no-daemon`, and the
+/// daemon itself).
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Actions answered from the cache while the guild's WebSocket is up.
+const CACHED: &[&str] = &["nowplaying", "queue"];
+
+/// Actions whose REST response is the point (results, lyrics, the current
+/// filters, and the queued or skipped tracks that the summary and the local
+/// history are built from). Other actions go over the WebSocket when it is up.
+const REST_ONLY: &[&str] = &["search", "lyrics", "filters", "play", "skip"];
+
+/// Events after which cached answers may be out of date.
+const INVALIDATE: &[&str] = &[
+    "initial_state",
+    "track_start",
+    "track_end",
+    "queue_update",
+    "player_update",
+];
+
+/// How long an action sent over the WebSocket may take to be answered.
+const ACTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a command waits for the daemon before giving up.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Request {
+    Audio {
+        base_url: String,
+        token: Option<String>,
+        payload: Value,
+    },
+    Status,
+    Stop,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Response {
+    Reply(Reply),
+    /// Not for this daemon; the command should ask the server itself
+    Fallback { reason: String },
+    Status(Status),
+    Stopping,
+    Error { message: String },
+}
+
+/// An answer to an audio action, as the server would have given it.
+#[derive(Serialize, Deserialize)]
+pub struct Reply {
+    status: u16,
+    pub body: String,
+}
+
+impl Reply {
+    fn new(status: StatusCode, body: String) -> Self {
+        Reply {
+            status: status.as_u16(),
+            body,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::BAD_GATEWAY)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Status {
+    pid: u32,
+    base_url: String,
+    uptime_secs: u64,
+    requests: u64,
+    cache_hits: u64,
+    guilds: Vec<GuildStatus>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GuildStatus {
+    guild_id: String,
+    connected: bool,
+    cached: usize,
+}
+
+/// Send `payload` through the daemon. `None` when there is no daemon to ask
+/// or it can't serve this request, in which case the caller does the REST
+/// call itself.
+pub async fn request<T: Serialize>(
+    base_url: &str,
+    token: Option<&str>,
+    payload: &T,
+) -> Result<Option<Reply>> {
+    if !enabled() {
+        return Ok(None);
+    }
+    let Some(stream) = connect().await else {
+        return Ok(None);
+    };
+    let request = Request::Audio {
+        base_url: base_url.to_string(),
+        token: token.map(str::to_string),
+        payload: serde_json::to_value(payload)?,
+    };
+    match exchange(stream, &request).await? {
+        Response::Reply(reply) => Ok(Some(reply)),
+        Response::Fallback { .. } => Ok(None),
+        Response::Error { message } => bail!("daemon: {}", message),
+        _ => bail!("unexpected answer from the daemon"),
+    }
+}
+
+/// Whether commands in this process will go through a daemon.
+pub fn is_running() -> bool {
+    #[cfg(unix)]
+    {
+        enabled()
+            && api::daemon_socket_path()
+                .is_some_and(|p| std::os::unix::net::UnixStream::connect(p).is_ok())
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+#[cfg(unix)]
+async fn connect() -> Option<Stream> {
+    Stream::connect(api::daemon_socket_path()?).await.ok()
+}
+
+#[cfg(not(unix))]
+async fn connect() -> Option<Stream> {
+    None
+}
+
+async fn exchange(stream: Stream, request: &Request) -> Result<Response> {
+    let talk = async {
+        let (read, mut write) = tokio::io::split(stream);
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        write.write_all(line.as_bytes()).await?;
+        let mut answer = String::new();
+        BufReader::new(read).read_line(&mut answer).await?;
+        if answer.is_empty() {
+            bail!("the daemon closed the connection");
+        }
+        Ok(serde_json::from_str(&answer)?)
+    };
+    tokio::time::timeout(REQUEST_TIMEOUT, talk)
+        .await
+        .context("the daemon did not answer in time")?
+        .context("talking to the daemon")
+}
+
+/// `jorik daemon status`
+pub async fn print_status() -> Result<()> {
+    let Some(stream) = connect().await else {
+        println!("{} No daemon running", "ℹ️".blue());
+        return Ok(());
+    };
+    let Response::Status(status) = exchange(stream, &Request::Status).await? else {
+        bail!("unexpected answer from the daemon");
+    };
+    println!(
+        "{} Daemon running (pid {}, up {}) for {}",
+        "🛰️".cyan(),
+        status.pid,
+        humantime::format_duration(Duration::from_secs(status.uptime_secs)),
+        status.base_url
+    );
+    for guild in &status.guilds {
+        let state = if guild.connected {
+            "connected".green()
+        } else {
+            "reconnecting".yellow()
+        };
+        println!(
+            "   Guild {}: {}, {} cached answer(s)",
+            guild.guild_id.bold(),
+            state,
+            guild.cached
+        );
+    }
+    println!(
+        "   {} request(s), {} from the cache",
+        status.requests, status.cache_hits
+    );
+    Ok(())
+}
+
+/// `jorik daemon stop`
+pub async fn stop() -> Result<()> {
+    let Some(stream) = connect().await else {
+        println!("{} No daemon running", "ℹ️".blue());
+        return Ok(());
+    };
+    exchange(stream, &Request::Stop).await?;
+    println!("{} Daemon stopped", "✔".green());
+    Ok(())
+}
+
+/// Run the daemon in the foreground until `jorik daemon stop`, Ctrl+C or
+/// SIGTERM.
+#[cfg(unix)]
+pub async fn run(
+    client: Client,
+    base_url: String,
+    token: Option<String>,
+    hooks: Option<hooks::Runner>,
+    guild_id: Option<String>,
+    user_id: Option<String>,
+) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::net::UnixListener;
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let token = token.context("the daemon needs a login; run `jorik auth login` first")?;
+    let path = api::daemon_socket_path().context("cannot determine the daemon socket path")?;
+    if connect().await.is_some() {
+        bail!("a daemon is already running on {}", path.display());
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+    // Left behind by a daemon that didn't shut down cleanly
+    let _ = std::fs::remove_file(&path);
+    let listener =
+        UnixListener::bind(&path).with_context(|| format!("listening on {}", path.display()))?;
+    // Whoever can connect can act with the token
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    set_enabled(false);
+
+    let subscribe = hooks.is_some() || guild_id.is_some();
+    let daemon = Arc::new(Daemon::new(client, base_url, token, hooks));
+    println!(
+        "{} Daemon listening on {} for {}",
+        "🛰️".cyan(),
+        path.display(),
+        daemon.base_url
+    );
+    if subscribe {
+        daemon.subscribe(guild_id, user_id).await;
+    }
+    let update_client = daemon.client.clone();
+    tokio::spawn(async move {
+        if let Some((latest, _)) = crate::check_for_updates(&update_client).await {
+            println!(
+                "{} jorik-cli {} is available (running {})",
+                "ℹ️".blue(),
+                latest.green().bold(),
+                env!("CARGO_PKG_VERSION")
+            );
+        }
+    });
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted.context("accepting a connection")?;
+                let daemon = daemon.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = daemon.serve(stream).await {
+                        eprintln!("{} {:#}", "✘".red(), e);
+                    }
+                });
+            }
+            _ = daemon.stop.notified() => break,
+            _ = tokio::signal::ctrl_c() => break,
+            _ = terminate.recv() => break,
+        }
+    }
+    let _ = std::fs::remove_file(&path);
+    println!("{} Daemon stopped", "🛰️".cyan());
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn run(
+    _client: Client,
+    _base_url: String,
+    _token: Option<String>,
+    _hooks: Option<hooks::Runner>,
+    _guild_id: Option<String>,
+    _user_id: Option<String>,
+) -> Result<()> {
+    bail!("the daemon needs Unix domain sockets, which this platform doesn't have")
+}
+
+struct Daemon {
+    client: Client,
+    base_url: String,
+    token: String,
+    hooks: Option<hooks::Runner>,
+    started: Instant,
+    stop: Notify,
+    next_id: AtomicU64,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Guild of requests that don't name one, once the server has said
+    default_guild: Option<String>,
+    guilds: HashMap<String, Guild>,
+    requests: u64,
+    cache_hits: u64,
+}
+
+struct Guild {
+    outgoing: UnboundedSender<String>,
+    connected: bool,
+    /// Answers keyed by the request payload
+    cache: HashMap<String, Cached>,
+    /// Last state_update without the clock, to tell ticks from changes
+    last_state: Option<Value>,
+    /// Actions sent over the WebSocket, by id, waiting for their
+    /// action_response
+    pending: HashMap<String, oneshot::Sender<Value>>,
+}
+
+struct Cached {
+    json: Value,
+    at: Instant,
+}
+
+impl Daemon {
+    fn new(client: Client, base_url: String, token: String, hooks: Option<hooks::Runner>) -> Self {
+        Daemon {
+            client,
+            base_url,
+            token,
+            hooks,
+            started: Instant::now(),
+            stop: Notify::new(),
+            next_id: AtomicU64::new(1),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    async fn serve<S: AsyncRead + AsyncWrite>(self: Arc<Self>, stream: S) -> Result<()> {
+        let (read, mut write) = tokio::io::split(stream);
+        let mut lines = BufReader::new(read).lines();
+        while let Some(line) = lines.next_line().await? {
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(Request::Audio {
+                    base_url,
+                    token,
+                    payload,
+                }) => {
+                    if base_url.trim_end_matches('/') != self.base_url.trim_end_matches('/') {
+                        Response::Fallback {
+                            reason: format!("serving {}", self.base_url),
+                        }
+                    } else if token.as_deref() != Some(self.token.as_str()) {
+                        Response::Fallback {
+                            reason: "different token".to_string(),
+                        }
+                    } else {
+                        self.audio(payload).await
+                    }
+                }
+                Ok(Request::Status) => Response::Status(self.status().await),
+                Ok(Request::Stop) => {
+                    self.stop.notify_one();
+                    Response::Stopping
+                }
+                Err(e) => Response::Error {
+                    message: format!("invalid request: {}", e),
+                },
+            };
+            let mut out = serde_json::to_string(&response)?;
+            out.push('\n');
+            write.write_all(out.as_bytes()).await?;
+        }
+        Ok(())
+    }
+
+    async fn audio(self: &Arc<Self>, payload: Value) -> Response {
+        let action = payload
+            .get("action")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let named = payload
+            .get("guild_id")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let key = payload.to_string();
+        let guild_id = {
+            let mut state = self.state.lock().await;
+            state.requests += 1;
+            named.clone().or_else(|| state.default_guild.clone())
+        };
+
+        if let Some(guild_id) = &guild_id {
+            if CACHED.contains(&action.as_str()) {
+                if let Some(json) = self.cached(guild_id, &key).await {
+                    log(&action, "cache");
+                    return Response::Reply(Reply::new(StatusCode::OK, json.to_string()));
+                }
+            } else if !REST_ONLY.contains(&action.as_str())
+                && let Some(result) = self.send_action(guild_id, &action, payload.clone()).await
+            {
+                log(&action, "websocket");
+                return match result {
+                    Ok(reply) => Response::Reply(reply),
+                    Err(message) => Response::Error { message },
+                };
+            }
+        }
+
+        log(&action, "rest");
+        let result = api::send_audio(&self.client, &self.base_url, Some(&self.token), &payload).await;
+        let (status, text) = match result {
+            Ok(resp) => {
+                let status = resp.status();
+                match resp.text().await {
+                    Ok(text) => (status, text),
+                    Err(e) => {
+                        return Response::Error {
+                            message: format!("reading response body: {}", e),
+                        };
+                    }
+                }
+            }
+            Err(e) => {
+                return Response::Error {
+                    message: format!("{:#}", e),
+                };
+            }
+        };
+        if status.is_success()
+            && let Ok(json) = serde_json::from_str::<Value>(&text)
+        {
+            self.remember(named.is_none(), guild_id, &action, key, json)
+                .await;
+        }
+        Response::Reply(Reply::new(status, text))
+    }
+
+    /// A cached answer, if the guild's WebSocket is up to keep it current.
+    async fn cached(&self, guild_id: &str, key: &str) -> Option<Value> {
+        let mut state = self.state.lock().await;
+        let guild = state.guilds.get(guild_id).filter(|g| g.connected)?;
+        let cached = guild.cache.get(key)?;
+        let mut json = cached.json.clone();
+        advance_clock(&mut json, cached.at.elapsed());
+        state.cache_hits += 1;
+        Some(json)
+    }
+
+    /// Learn from a successful REST answer: the guild it is about, what to
+    /// cache, and what it made stale.
+    async fn remember(
+        self: &Arc<Self>,
+        unnamed: bool,
+        guild_id: Option<String>,
+        action: &str,
+        key: String,
+        json: Value,
+    ) {
+        let guild_id = ws::guild_from_response(&json).or(guild_id);
+        let mut state = self.state.lock().await;
+        if unnamed && let Some(id) = &guild_id {
+            state.default_guild = Some(id.clone());
+        }
+        let Some(guild_id) = guild_id else {
+            return;
+        };
+        let guild = state
+            .guilds
+            .entry(guild_id.clone())
+            .or_insert_with(|| self.watch(guild_id));
+        if CACHED.contains(&action) {
+            guild.cache.insert(
+                key,
+                Cached {
+                    json,
+                    at: Instant::now(),
+                },
+            );
+        } else {
+            guild.cache.clear();
+        }
+    }
+
+    /// Send an action over the guild's WebSocket and wait for the server's
+    /// answer. `None` if it isn't connected, so the caller uses REST.
+    async fn send_action(
+        &self,
+        guild_id: &str,
+        action: &str,
+        mut payload: Value,
+    ) -> Option<Result<Reply, String>> {
+        let id = format!("daemon-{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+        let (tx, rx) = oneshot::channel();
+        {
+            let mut state = self.state.lock().await;
+            let guild = state.guilds.get_mut(guild_id).filter(|g| g.connected)?;
+            if payload.get("guild_id").is_none_or(Value::is_null) {
+                payload["guild_id"] = json!(guild_id);
+            }
+            let message = WsAction {
+                event_type: "action",
+                id: id.clone(),
+                payload: &payload,
+            };
+            guild.outgoing.send(serde_json::to_string(&message).ok()?).ok()?;
+            guild.pending.insert(id.clone(), tx);
+            guild.cache.clear();
+        }
+
+        let answer = match tokio::time::timeout(ACTION_TIMEOUT, rx).await {
+            Ok(Ok(answer)) => answer,
+            Ok(Err(_)) => {
+                return Some(Err(format!(
+                    "the WebSocket closed before the server answered {}",
+                    action
+                )));
+            }
+            Err(_) => {
+                if let Some(guild) = self.state.lock().await.guilds.get_mut(guild_id) {
+                    guild.pending.remove(&id);
+                }
+                return Some(Err(format!("the server did not answer {} in time", action)));
+            }
+        };
+        // The REST body when the server sends it along. Without it there is
+        // nothing to summarise, and a bare `{"action": ...}` would summarise
+        // wrongly (e.g. "Nothing to skip"); actions that need their body are
+        // in `REST_ONLY`.
+        let body = answer
+            .get("data")
+            .filter(|d| d.is_object())
+            .cloned()
+            .unwrap_or_else(|| json!({ "success": true }));
+        if answer.get("success").and_then(Value::as_bool) == Some(false) {
+            let message = answer
+                .get("error")
+                .or_else(|| answer.get("message"))
+                .and_then(Value::as_str)
+                .unwrap_or("the server rejected the action");
+            let body = json!({ "error": "action_failed", "message": message });
+            return Some(Ok(Reply::new(StatusCode::BAD_REQUEST, body.to_string())));
+        }
+        Some(Ok(Reply::new(StatusCode::OK, body.to_string())))
+    }
+
+    /// Watch a guild from the start instead of once a command names it, so
+    /// hooks run without any other jorik command having been used.
+    async fn subscribe(self: &Arc<Self>, guild_id: Option<String>, user_id: Option<String>) {
+        let guild_id = match guild_id {
+            Some(id) => Ok(id),
+            None => ws::discover_guild(&self.client, &self.base_url, &self.token, user_id).await,
+        };
+        let guild_id = match guild_id {
+            Ok(id) => id,
+            Err(e) => {
+                eprintln!("{} {:#}; hooks start once a command names a guild", "⚠️".yellow(), e);
+                return;
+            }
+        };
+        if let Some(hooks) = &self.hooks {
+            println!(
+                "{} Running {} hook(s) for guild {}",
+                "🪝".cyan(),
+                hooks.count(),
+                guild_id.bold()
+            );
+        }
+        let mut state = self.state.lock().await;
+        state.default_guild = Some(guild_id.clone());
+        let guild = self.watch(guild_id.clone());
+        state.guilds.entry(guild_id).or_insert(guild);
+    }
+
+    /// Subscribe to a guild's events, keeping its entry in the state up to
+    /// date from a background task.
+    fn watch(self: &Arc<Self>, guild_id: String) -> Guild {
+        let (outgoing, updates) =
+            ws::subscribe_duplex(self.base_url.clone(), self.token.clone(), guild_id.clone());
+        tokio::spawn(self.clone().follow(guild_id, updates));
+        Guild {
+            outgoing,
+            connected: false,
+            cache: HashMap::new(),
+            last_state: None,
+            pending: HashMap::new(),
+        }
+    }
+
+    async fn follow(self: Arc<Self>, guild_id: String, mut updates: UnboundedReceiver<Update>) {
+        while let Some(update) = updates.recv().await {
+            match &update {
+                Update::Connected => {
+                    println!("{} Connected to guild {}", "🔌".green(), guild_id.bold());
+                }
+                Update::Disconnected(reason) => eprintln!(
+                    "{} Guild {} disconnected ({}), retrying in {}s",
+                    "⚠️".yellow(),
+                    guild_id,
+                    reason,
+                    ws::RECONNECT_DELAY.as_secs()
+                ),
+                Update::Event { event, raw } => {
+                    if let Some(hooks) = &self.hooks
+                        && event.guild_id.as_deref().is_none_or(|g| g == guild_id)
+                    {
+                        hooks.dispatch(&event.event_type, raw);
+                    }
+                }
+            }
+            let mut state = self.state.lock().await;
+            let Some(guild) = state.guilds.get_mut(&guild_id) else {
+                return;
+            };
+            guild.apply(&guild_id, update);
+        }
+    }
+
+    async fn status(&self) -> Status {
+        let state = self.state.lock().await;
+        let mut guilds: Vec<GuildStatus> = state
+            .guilds
+            .iter()
+            .map(|(id, g)| GuildStatus {
+                guild_id: id.clone(),
+                connected: g.connected,
+                cached: g.cache.len(),
+            })
+            .collect();
+        guilds.sort_by(|a, b| a.guild_id.cmp(&b.guild_id));
+        Status {
+            pid: std::process::id(),
+            base_url: self.base_url.clone(),
+            uptime_secs: self.started.elapsed().as_secs(),
+            requests: state.requests,
+            cache_hits: state.cache_hits,
+            guilds,
+        }
+    }
+}
+
+impl Guild {
+    fn apply(&mut self, guild_id: &str, update: Update) {
+        let (event, raw) = match update {
+            // Anything may have happened while we were away
+            Update::Connected => {
+                self.connected = true;
+                self.cache.clear();
+                return;
+            }
+            Update::Disconnected(_) => {
+                self.connected = false;
+                self.cache.clear();
+                self.last_state = None;
+                // Dropping the senders fails the waiting actions
+                self.pending.clear();
+                return;
+            }
+            Update::Event { event, raw } => (event, raw),
+        };
+        if event.guild_id.as_deref().is_some_and(|g| g != guild_id) {
+            return;
+        }
+        match event.event_type.as_str() {
+            "action_response" => {
+                if let Some(tx) = event.id.as_ref().and_then(|id| self.pending.remove(id)) {
+                    let _ = tx.send(raw);
+                }
+            }
+            "state_update" => {
+                let state = event.data.as_ref().map(follow::stable);
+                if state != self.last_state {
+                    self.last_state = state;
+                    self.cache.clear();
+                } else if let Some(playback) = ws::playback(&event) {
+                    self.tick(&playback);
+                }
+            }
+            t if INVALIDATE.contains(&t) => self.cache.clear(),
+            _ => {}
+        }
+    }
+
+    /// Move the clock of cached `nowplaying` answers to what the server
+    /// just reported.
+    fn tick(&mut self, playback: &PlaybackState) {
+        for cached in self.cache.values_mut() {
+            if let Some(np) = cached.json.get_mut("now_playing").filter(|np| np.is_object()) {
+                np["elapsedMs"] = json!(playback.elapsed_ms);
+                np["paused"] = json!(playback.paused);
+                cached.at = Instant::now();
+            }
+        }
+    }
+}
+
+/// Account for the time a `nowplaying` answer spent in the cache.
+fn advance_clock(json: &mut Value, age: Duration) {
+    let Some(np) = json.get_mut("now_playing").filter(|np| np.is_object()) else {
+        return;
+    };
+    if np.get("paused").and_then(Value::as_bool).unwrap_or(false) {
+        return;
+    }
+    let (Some(elapsed), Some(duration)) = (
+        np.get("elapsedMs").and_then(Value::as_u64),
+        np.get("durationMs").and_then(Value::as_u64),
+    ) else {
+        return;
+    };
+    np["elapsedMs"] = json!((elapsed + age.as_millis() as u64).min(duration));
+}
+
+fn log(action: &str, via: &str) {
+    println!(
+        "{}",
+        format!(
+            "{} {} via {}",
+            chrono::Local::now().format("%H:%M:%S"),
+            action,
+            via
+        )
+        .dimmed()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advances_cached_clocks() {
+        let nowplaying = |elapsed: u64, paused: bool| {
+            json!({"now_playing": {"elapsedMs": elapsed, "durationMs": 10_000, "paused": paused}})
+        };
+        let cases = [
+            (nowplaying(1000, false), nowplaying(3000, false)),
+            // paused tracks stand still, the end is the limit
+            (nowplaying(1000, true), nowplaying(1000, true)),
+            (nowplaying(9000, false), nowplaying(10_000, false)),
+            (json!({"now_playing": null}), json!({"now_playing": null})),
+        ];
+        for (mut cached, expected) in cases {
+            advance_clock(&mut cached, Duration::from_secs(2));
+            assert_eq!(cached, expected);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! `jorik events`: tail the raw WebSocket events for a guild, optionally
+//! running the configured [`hooks`] for them.
+
+use crate::follow::Output;
+use crate::hooks;
+use crate::ws::{self, Update};
+use anyhow::{Context, Result};
+use colored::Colorize;
+use colored_json::ToColoredJson;
+use reqwest::Client;
+use serde_json::{Value, json};
+use std::time::Instant;
+
+pub struct Options {
+    /// Only these event types; empty means all
+    pub types: Vec<String>,
+    pub guild_id: Option<String>,
+    pub user_id: Option<String>,
+    /// Timestamp events relative to the current connection
+    pub since_connect: bool,
+    pub no_spectrogram: bool,
+    pub output: Output,
+    /// Run hooks for every event of the guild, shown or not
+    pub hooks: Option<hooks::Runner>,
+}
+
+pub async fn run(client: &Client, base_url: &str, token: Option<&str>, opts: Options) -> Result<()> {
+    let token = token.context("events need a login; run `jorik auth login` first")?;
+    let guild_id = match opts.guild_id.clone() {
+        Some(id) => id,
+        None => ws::discover_guild(client, base_url, token, opts.user_id.clone()).await?,
+    };
+
+    let mut updates = ws::subscribe(base_url.to_string(), token.to_string(), guild_id.clone());
+    let mut connected_at = Instant::now();
+    while let Some(update) = updates.recv().await {
+        match update {
+            Update::Connected => {
+                connected_at = Instant::now();
+                eprintln!("{} Subscribed to guild {}", "🔌".green(), guild_id.bold());
+            }
+            Update::Disconnected(reason) => eprintln!(
+                "{} WebSocket disconnected ({}), retrying in {}s",
+                "⚠️".yellow(),
+                reason,
+                ws::RECONNECT_DELAY.as_secs()
+            ),
+            Update::Event { event, mut raw } => {
+                let event_type = event.event_type.as_str();
+                if event.guild_id.as_deref().is_some_and(|g| g != guild_id) {
+                    continue;
+                }
+                if let Some(hooks) = &opts.hooks {
+                    hooks.dispatch(event_type, &raw);
+                }
+                if !opts.types.is_empty() && !opts.types.iter().any(|t| t == event_type) {
+                    continue;
+                }
+                if opts.no_spectrogram {
+                    if event_type == "spectrogram_update" {
+                        continue;
+                    }
+                    strip_spectrogram(&mut raw);
+                }
+                let since_connect = opts.since_connect.then(|| connected_at.elapsed());
+                print_event(event_type, &raw, since_connect, opts.output)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_event(
+    event_type: &str,
+    raw: &Value,
+    since_connect: Option<std::time::Duration>,
+    output: Output,
+) -> Result<()> {
+    match output {
+        Output::Ndjson => {
+            let mut line = json!({
+                "at": chrono::Local::now().to_rfc3339(),
+                "event": raw,
+            });
+            if let Some(elapsed) = since_connect {
+                line["since_connect_ms"] = json!(elapsed.as_millis() as u64);
+            }
+            println!("{}", line);
+        }
+        Output::Text => {
+            let stamp = match since_connect {
+                Some(elapsed) => format!("+{:.3}s", elapsed.as_secs_f64()),
+                None => chrono::Local::now().format("%H:%M:%S%.3f").to_string(),
+            };
+            println!("{} {}", stamp.dimmed(), event_type.cyan().bold());
+            println!("{}", serde_json::to_string_pretty(raw)?.to_colored_json_auto()?);
+        }
+    }
+    Ok(())
+}
+
+/// Spectrograms are large and mostly noise when reading events.
+fn strip_spectrogram(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.remove("spectrogram");
+            map.values_mut().for_each(strip_spectrogram);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_spectrogram),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_nested_spectrograms() {
+        let mut event = json!({
+            "type": "player_update",
+            "spectrogram": [1, 2],
+            "data": {"elapsedMs": 5, "spectrogram": [3], "bands": [{"spectrogram": [4], "hz": 60}]},
+        });
+        strip_spectrogram(&mut event);
+        assert_eq!(
+            event,
+            json!({"type": "player_update", "data": {"elapsedMs": 5, "bands": [{"hz": 60}]}})
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! Favorites library, stored per profile in `profiles/<name>/favorites.json`.
+
+use crate::api::profile_dir_path;
+use anyhow::{Context, Result, bail};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Favorite {
+    pub name: String,
+    /// URI or search query sent to `play`
+    pub query: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Unix timestamp in seconds
+    #[serde(default)]
+    pub added_at: i64,
+}
+
+impl Favorite {
+    /// Build a favorite from a track object as returned by `nowplaying`.
+    pub fn from_track(track: &Value) -> Option<Self> {
+        let track = track.get("track").unwrap_or(track);
+        let uri = track.get("uri").and_then(|v| v.as_str())?;
+        let title = track
+            .get("title")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+        let author = track
+            .get("author")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+        Some(Favorite {
+            name: title.clone().unwrap_or_else(|| uri.to_string()),
+            query: uri.to_string(),
+            title,
+            author,
+            tags: Vec::new(),
+            added_at: chrono::Local::now().timestamp(),
+        })
+    }
+
+    pub fn from_query(query: &str) -> Self {
+        Favorite {
+            name: query.to_string(),
+            query: query.to_string(),
+            title: None,
+            author: None,
+            tags: Vec::new(),
+            added_at: chrono::Local::now().timestamp(),
+        }
+    }
+
+    pub fn display_title(&self) -> String {
+        match (&self.title, &self.author) {
+            (Some(t), Some(a)) => format!("{} by {}", t, a),
+            (Some(t), None) => t.clone(),
+            _ => self.query.clone(),
+        }
+    }
+}
+
+fn favorites_path(profile: &str) -> Option<PathBuf> {
+    profile_dir_path(profile).map(|p| p.join("favorites.json"))
+}
+
+pub fn load(profile: &str) -> Vec<Favorite> {
+    favorites_path(profile)
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(profile: &str, favorites: &[Favorite]) -> Result<()> {
+    let path = favorites_path(profile).context("cannot determine favorites path")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context("creating profile directory")?;
+    }
+    let json = serde_json::to_string_pretty(favorites).context("serializing favorites")?;
+    fs::write(&path, json).context("writing favorites file")?;
+    Ok(())
+}
+
+/// Add a favorite. Starring something that is already a favorite (same query)
+/// merges the tags instead of adding a duplicate. Returns the stored entry.
+pub fn add(profile: &str, mut favorite: Favorite) -> Result<Favorite> {
+    let mut favorites = load(profile);
+    if let Some(existing) = favorites.iter_mut().find(|f| f.query == favorite.query) {
+        for tag in favorite.tags {
+            if !existing.tags.contains(&tag) {
+                existing.tags.push(tag);
+            }
+        }
+        let stored = existing.clone();
+        save(profile, &favorites)?;
+        return Ok(stored);
+    }
+    if favorites.iter().any(|f| f.name == favorite.name) {
+        bail!(
+            "a favorite named {:?} already exists (use --name to pick another)",
+            favorite.name
+        );
+    }
+    favorite.tags.sort();
+    favorite.tags.dedup();
+    favorites.push(favorite.clone());
+    save(profile, &favorites)?;
+    Ok(favorite)
+}
+
+pub fn remove(profile: &str, name: &str) -> Result<Favorite> {
+    let mut favorites = load(profile);
+    let idx = favorites
+        .iter()
+        .position(|f| f.name == name)
+        .with_context(|| format!("no favorite named {:?}", name))?;
+    let removed = favorites.remove(idx);
+    save(profile, &favorites)?;
+    Ok(removed)
+}
+
+/// Resolve `name_or_tag` to the favorites to play: an exact name match wins,
+/// otherwise every favorite carrying that tag.
+pub fn select(profile: &str, name_or_tag: &str, shuffle: bool) -> Result<Vec<Favorite>> {
+    let favorites = load(profile);
+    if let Some(fav) = favorites.iter().find(|f| f.name == name_or_tag) {
+        return Ok(vec![fav.clone()]);
+    }
+    let mut tagged: Vec<Favorite> = favorites
+        .into_iter()
+        .filter(|f| f.tags.iter().any(|t| t == name_or_tag))
+        .collect();
+    if tagged.is_empty() {
+        bail!("no favorite or tag named {:?}", name_or_tag);
+    }
+    if shuffle {
+        tagged.shuffle(&mut rand::rng());
+    }
+    Ok(tagged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn builds_favorites_from_tracks() {
+        let cases = [
+            (
+                json!({"track": {"uri": "https://youtu.be/a", "title": "A", "author": "X"}}),
+                Some(("A", "A by X")),
+            ),
+            (json!({"uri": "https://youtu.be/a", "title": "A"}), Some(("A", "A"))),
+            (json!({"uri": "ytsearch:a"}), Some(("ytsearch:a", "ytsearch:a"))),
+            (json!({"title": "no uri"}), None),
+        ];
+        for (track, expected) in cases {
+            let favorite = Favorite::from_track(&track);
+            assert_eq!(
+                favorite.as_ref().map(|f| (f.name.as_str(), f.display_title())),
+                expected.map(|(name, title)| (name, title.to_string())),
+                "{}",
+                track
+            );
+        }
+    }
+}
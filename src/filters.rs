@@ -0,0 +1,816 @@
+//! Audio filter presets and the `filter custom` argument parsing.
+//!
+//! Presets come from two places: the built-in table below and TOML/JSON files
+//! in the `presets/` directory next to `settings.json`. A user preset with the
+//! same name as a built-in one replaces it, so a team can ship its own tuned
+//! `bassboost` as a shared file.
+//!
+//! Value parsers here only check the shape of a flag (`band:gain`,
+//! `speed,pitch,rate`, ...). Range checks live in [`validate`] so that
+//! filters loaded from JSON go through exactly the same rules as flags.
+
+use crate::api::{
+    AudioFilters, ChannelMixOptions, DistortionOptions, EqualizerBand, KaraokeOptions,
+    LowPassOptions, RotationOptions, TimescaleOptions, TremoloOptions, VibratoOptions,
+};
+use anyhow::{Context, Result, anyhow, bail};
+use clap::{Args, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Names of the presets compiled into the binary.
+pub const BUILTIN_PRESETS: &[&str] = &[
+    "clear",
+    "bassboost",
+    "nightcore",
+    "vaporwave",
+    "8d",
+    "soft",
+    "tremolo",
+    "vibrato",
+    "karaoke",
+];
+
+/// Number of equalizer bands supported by the server.
+pub const EQ_BANDS: i32 = 15;
+
+/// Look up a built-in preset by (case-insensitive) name.
+pub fn builtin(name: &str) -> Option<AudioFilters> {
+    let filters = match name.to_lowercase().as_str() {
+        "clear" => AudioFilters::default(),
+        "bassboost" => AudioFilters {
+            equalizer: Some(vec![
+                EqualizerBand { band: 0, gain: 0.2 },
+                EqualizerBand { band: 1, gain: 0.15 },
+                EqualizerBand { band: 2, gain: 0.1 },
+                EqualizerBand { band: 3, gain: 0.05 },
+                EqualizerBand { band: 4, gain: 0.0 },
+                EqualizerBand { band: 5, gain: -0.05 },
+            ]),
+            ..Default::default()
+        },
+        "soft" => AudioFilters {
+            low_pass: Some(LowPassOptions { smoothing: Some(20.0) }),
+            ..Default::default()
+        },
+        "nightcore" => AudioFilters {
+            timescale: Some(TimescaleOptions { speed: Some(1.1), pitch: Some(1.1), rate: Some(1.0) }),
+            ..Default::default()
+        },
+        "vaporwave" => AudioFilters {
+            timescale: Some(TimescaleOptions { speed: Some(0.85), pitch: Some(0.8), rate: Some(1.0) }),
+            ..Default::default()
+        },
+        "8d" => AudioFilters {
+            rotation: Some(RotationOptions { rotation_hz: Some(0.2) }),
+            ..Default::default()
+        },
+        "tremolo" => AudioFilters {
+            tremolo: Some(TremoloOptions { frequency: Some(2.0), depth: Some(0.5) }),
+            ..Default::default()
+        },
+        "vibrato" => AudioFilters {
+            vibrato: Some(VibratoOptions { frequency: Some(2.0), depth: Some(0.5) }),
+            ..Default::default()
+        },
+        "karaoke" => AudioFilters {
+            karaoke: Some(KaraokeOptions { level: Some(1.0), mono_level: Some(1.0), filter_band: Some(220.0), filter_width: Some(100.0) }),
+            ..Default::default()
+        },
+        _ => return None,
+    };
+    Some(filters)
+}
+
+/// A named filter set, either built in or loaded from the presets directory.
+#[derive(Clone, Debug)]
+pub struct Preset {
+    pub name: String,
+    pub description: Option<String>,
+    pub filters: AudioFilters,
+    /// File the preset was loaded from; `None` for built-in presets.
+    pub path: Option<PathBuf>,
+}
+
+impl Preset {
+    pub fn is_builtin(&self) -> bool {
+        self.path.is_none()
+    }
+}
+
+/// On-disk layout of a preset file. The preset name is the file stem.
+#[derive(Serialize, Deserialize)]
+struct PresetFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default)]
+    filters: AudioFilters,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum PresetFormat {
+    Toml,
+    Json,
+}
+
+impl PresetFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            PresetFormat::Toml => "toml",
+            PresetFormat::Json => "json",
+        }
+    }
+}
+
+/// Load built-in presets merged with the user's preset files.
+///
+/// Files that fail to parse or validate are skipped and reported in the second
+/// element so callers can decide whether to surface them.
+pub fn load_presets() -> (Vec<Preset>, Vec<anyhow::Error>) {
+    let mut presets: Vec<Preset> = BUILTIN_PRESETS
+        .iter()
+        .filter_map(|name| {
+            builtin(name).map(|filters| Preset {
+                name: name.to_string(),
+                description: None,
+                filters,
+                path: None,
+            })
+        })
+        .collect();
+    let mut errors = Vec::new();
+
+    let entries = crate::api::presets_dir_path().and_then(|dir| fs::read_dir(dir).ok());
+    let mut paths: Vec<PathBuf> = entries
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| matches!(p.extension().and_then(|e| e.to_str()), Some("toml" | "json")))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        match load_preset_file(&path) {
+            Ok(preset) => {
+                presets.retain(|p| !p.name.eq_ignore_ascii_case(&preset.name));
+                presets.push(preset);
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    (presets, errors)
+}
+
+fn load_preset_file(path: &Path) -> Result<Preset> {
+    let name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .with_context(|| format!("invalid preset file name {}", path.display()))?
+        .to_string();
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("reading preset {}", path.display()))?;
+    let file: PresetFile = if path.extension().and_then(|e| e.to_str()) == Some("toml") {
+        toml::from_str(&contents).with_context(|| format!("parsing preset {}", path.display()))?
+    } else {
+        serde_json::from_str(&contents).with_context(|| format!("parsing preset {}", path.display()))?
+    };
+    validate(&file.filters).with_context(|| format!("invalid preset {}", path.display()))?;
+    Ok(Preset {
+        name,
+        description: file.description,
+        filters: file.filters,
+        path: Some(path.to_path_buf()),
+    })
+}
+
+/// Find a preset by (case-insensitive) name, user presets first.
+pub fn find_preset(name: &str) -> Option<Preset> {
+    load_presets()
+        .0
+        .into_iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+/// Write a user preset, replacing any existing file with the same name.
+pub fn save_preset(
+    name: &str,
+    description: Option<String>,
+    filters: AudioFilters,
+    format: PresetFormat,
+) -> Result<PathBuf> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        || name.starts_with('.')
+    {
+        bail!("invalid preset name {:?}: use letters, digits, '-', '_' or '.'", name);
+    }
+    validate(&filters)?;
+
+    let dir = crate::api::presets_dir_path().context("cannot determine presets directory")?;
+    fs::create_dir_all(&dir).context("creating presets directory")?;
+    remove_user_preset_files(&dir, name)?;
+
+    let contents = render_preset(&PresetFile { description, filters }, format)?;
+    let path = dir.join(format!("{}.{}", name, format.extension()));
+    fs::write(&path, contents).context("writing preset file")?;
+    Ok(path)
+}
+
+fn render_preset(file: &PresetFile, format: PresetFormat) -> Result<String> {
+    let json = serde_json::to_string_pretty(file).context("serializing preset")?;
+    match format {
+        // Round-trip through JSON text so f32 values are written as `0.3`
+        // rather than their widened f64 representation.
+        PresetFormat::Toml => {
+            let value: serde_json::Value = serde_json::from_str(&json).context("serializing preset")?;
+            toml::to_string_pretty(&value).context("serializing preset")
+        }
+        PresetFormat::Json => Ok(json),
+    }
+}
+
+/// Delete a user preset. Built-in presets cannot be deleted.
+pub fn delete_preset(name: &str) -> Result<Vec<PathBuf>> {
+    let dir = crate::api::presets_dir_path().context("cannot determine presets directory")?;
+    let removed = remove_user_preset_files(&dir, name)?;
+    if removed.is_empty() {
+        if builtin(name).is_some() {
+            bail!("{} is a built-in preset and cannot be deleted", name);
+        }
+        return Err(anyhow!("no user preset named {}", name));
+    }
+    Ok(removed)
+}
+
+fn remove_user_preset_files(dir: &Path, name: &str) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for ext in ["toml", "json"] {
+        let path = dir.join(format!("{}.{}", name, ext));
+        if path.exists() {
+            fs::remove_file(&path).with_context(|| format!("removing {}", path.display()))?;
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
+/// Flags accepted by `jorik filter custom`.
+#[derive(Args, Debug, Clone)]
+pub struct CustomFilterArgs {
+    /// Start from a full filter object stored in a JSON file; other flags override its fields
+    #[arg(long, value_name = "FILE")]
+    pub from_json: Option<PathBuf>,
+    /// Player volume multiplier (0.0 - 5.0)
+    #[arg(long)]
+    pub volume: Option<f32>,
+    /// Equalizer band gain, repeatable (band 0-14, gain -0.25 - 1.0)
+    #[arg(long = "eq", value_name = "BAND:GAIN", value_parser = parse_eq_band)]
+    pub eq: Vec<EqualizerBand>,
+    /// Timescale as SPEED,PITCH,RATE
+    #[arg(long, value_name = "SPEED,PITCH,RATE", value_parser = parse_timescale)]
+    pub timescale: Option<TimescaleOptions>,
+    /// Tremolo as FREQUENCY,DEPTH
+    #[arg(long, value_name = "FREQUENCY,DEPTH", value_parser = parse_tremolo)]
+    pub tremolo: Option<TremoloOptions>,
+    /// Vibrato as FREQUENCY,DEPTH
+    #[arg(long, value_name = "FREQUENCY,DEPTH", value_parser = parse_vibrato)]
+    pub vibrato: Option<VibratoOptions>,
+    /// Rotation speed in Hz (8D audio)
+    #[arg(long, value_name = "HZ", value_parser = parse_rotation)]
+    pub rotation: Option<RotationOptions>,
+    /// Distortion as SIN_OFFSET,SIN_SCALE,COS_OFFSET,COS_SCALE,TAN_OFFSET,TAN_SCALE,OFFSET,SCALE
+    #[arg(long, value_name = "8 VALUES", value_parser = parse_distortion)]
+    pub distortion: Option<DistortionOptions>,
+    /// Channel mix as LEFT_TO_LEFT,LEFT_TO_RIGHT,RIGHT_TO_LEFT,RIGHT_TO_RIGHT
+    #[arg(long, value_name = "LL,LR,RL,RR", value_parser = parse_channel_mix)]
+    pub channel_mix: Option<ChannelMixOptions>,
+    /// Low-pass smoothing factor (greater than 1.0)
+    #[arg(long, value_name = "SMOOTHING", value_parser = parse_low_pass)]
+    pub low_pass: Option<LowPassOptions>,
+    /// Karaoke as LEVEL,MONO_LEVEL,FILTER_BAND,FILTER_WIDTH
+    #[arg(long, value_name = "LEVEL,MONO,BAND,WIDTH", value_parser = parse_karaoke)]
+    pub karaoke: Option<KaraokeOptions>,
+}
+
+impl CustomFilterArgs {
+    /// Build and validate the filter object described by the flags.
+    pub fn build(&self) -> Result<AudioFilters> {
+        let mut filters = match &self.from_json {
+            Some(path) => load_json(path)?,
+            None => AudioFilters::default(),
+        };
+
+        let overlay = AudioFilters {
+            volume: self.volume,
+            equalizer: (!self.eq.is_empty()).then(|| self.eq.clone()),
+            karaoke: self.karaoke.clone(),
+            timescale: self.timescale.clone(),
+            tremolo: self.tremolo.clone(),
+            vibrato: self.vibrato.clone(),
+            rotation: self.rotation.clone(),
+            distortion: self.distortion.clone(),
+            channel_mix: self.channel_mix.clone(),
+            low_pass: self.low_pass.clone(),
+        };
+        merge(&mut filters, &overlay);
+
+        validate(&filters)?;
+        Ok(filters)
+    }
+}
+
+/// The individual filter kinds that make up an [`AudioFilters`] object.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum FilterKind {
+    Volume,
+    #[value(alias = "eq")]
+    Equalizer,
+    Karaoke,
+    Timescale,
+    Tremolo,
+    Vibrato,
+    #[value(alias = "8d")]
+    Rotation,
+    Distortion,
+    ChannelMix,
+    LowPass,
+}
+
+impl FilterKind {
+    pub const ALL: [FilterKind; 10] = [
+        FilterKind::Volume,
+        FilterKind::Equalizer,
+        FilterKind::Karaoke,
+        FilterKind::Timescale,
+        FilterKind::Tremolo,
+        FilterKind::Vibrato,
+        FilterKind::Rotation,
+        FilterKind::Distortion,
+        FilterKind::ChannelMix,
+        FilterKind::LowPass,
+    ];
+
+    /// Short upper-case label used for badges.
+    pub fn label(self) -> &'static str {
+        match self {
+            FilterKind::Volume => "VOL",
+            FilterKind::Equalizer => "EQ",
+            FilterKind::Karaoke => "KARAOKE",
+            FilterKind::Timescale => "TIMESCALE",
+            FilterKind::Tremolo => "TREMOLO",
+            FilterKind::Vibrato => "VIBRATO",
+            FilterKind::Rotation => "8D",
+            FilterKind::Distortion => "DISTORT",
+            FilterKind::ChannelMix => "MIX",
+            FilterKind::LowPass => "LOWPASS",
+        }
+    }
+
+    pub fn is_set(self, filters: &AudioFilters) -> bool {
+        match self {
+            FilterKind::Volume => filters.volume.is_some(),
+            FilterKind::Equalizer => filters.equalizer.as_ref().is_some_and(|b| !b.is_empty()),
+            FilterKind::Karaoke => filters.karaoke.is_some(),
+            FilterKind::Timescale => filters.timescale.is_some(),
+            FilterKind::Tremolo => filters.tremolo.is_some(),
+            FilterKind::Vibrato => filters.vibrato.is_some(),
+            FilterKind::Rotation => filters.rotation.is_some(),
+            FilterKind::Distortion => filters.distortion.is_some(),
+            FilterKind::ChannelMix => filters.channel_mix.is_some(),
+            FilterKind::LowPass => filters.low_pass.is_some(),
+        }
+    }
+
+    pub fn clear(self, filters: &mut AudioFilters) {
+        match self {
+            FilterKind::Volume => filters.volume = None,
+            FilterKind::Equalizer => filters.equalizer = None,
+            FilterKind::Karaoke => filters.karaoke = None,
+            FilterKind::Timescale => filters.timescale = None,
+            FilterKind::Tremolo => filters.tremolo = None,
+            FilterKind::Vibrato => filters.vibrato = None,
+            FilterKind::Rotation => filters.rotation = None,
+            FilterKind::Distortion => filters.distortion = None,
+            FilterKind::ChannelMix => filters.channel_mix = None,
+            FilterKind::LowPass => filters.low_pass = None,
+        }
+    }
+}
+
+/// Filter kinds that are currently set in `filters`.
+pub fn active_kinds(filters: &AudioFilters) -> Vec<FilterKind> {
+    FilterKind::ALL
+        .into_iter()
+        .filter(|k| k.is_set(filters))
+        .collect()
+}
+
+/// Stack `overlay` on top of `base`: every kind set in the overlay replaces the
+/// base one, except equalizer bands which are merged band by band.
+pub fn merge(base: &mut AudioFilters, overlay: &AudioFilters) {
+    if let Some(volume) = overlay.volume {
+        base.volume = Some(volume);
+    }
+    if let Some(overlay_bands) = &overlay.equalizer {
+        let mut bands = base.equalizer.take().unwrap_or_default();
+        for band in overlay_bands {
+            bands.retain(|b| b.band != band.band);
+            bands.push(band.clone());
+        }
+        bands.sort_by_key(|b| b.band);
+        base.equalizer = Some(bands);
+    }
+    if let Some(v) = &overlay.karaoke {
+        base.karaoke = Some(v.clone());
+    }
+    if let Some(v) = &overlay.timescale {
+        base.timescale = Some(v.clone());
+    }
+    if let Some(v) = &overlay.tremolo {
+        base.tremolo = Some(v.clone());
+    }
+    if let Some(v) = &overlay.vibrato {
+        base.vibrato = Some(v.clone());
+    }
+    if let Some(v) = &overlay.rotation {
+        base.rotation = Some(v.clone());
+    }
+    if let Some(v) = &overlay.distortion {
+        base.distortion = Some(v.clone());
+    }
+    if let Some(v) = &overlay.channel_mix {
+        base.channel_mix = Some(v.clone());
+    }
+    if let Some(v) = &overlay.low_pass {
+        base.low_pass = Some(v.clone());
+    }
+}
+
+/// Extract the active filter set from a server response or WS payload.
+///
+/// Accepts both a top-level `filters` object and one nested under `data`.
+pub fn from_response(json: &serde_json::Value) -> Option<AudioFilters> {
+    let value = json
+        .get("filters")
+        .or_else(|| json.get("data").and_then(|d| d.get("filters")))?;
+    serde_json::from_value(value.clone()).ok()
+}
+
+fn load_json(path: &Path) -> Result<AudioFilters> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("reading filter file {}", path.display()))?;
+    serde_json::from_str(&contents)
+        .with_context(|| format!("parsing filter file {}", path.display()))
+}
+
+/// Range-check every field of a filter object.
+pub fn validate(filters: &AudioFilters) -> Result<()> {
+    if let Some(volume) = filters.volume {
+        check_range("volume", volume, 0.0, 5.0)?;
+    }
+    if let Some(bands) = &filters.equalizer {
+        for b in bands {
+            if !(0..EQ_BANDS).contains(&b.band) {
+                bail!("equalizer band {} is out of range (0-{})", b.band, EQ_BANDS - 1);
+            }
+            check_range(&format!("equalizer band {} gain", b.band), b.gain, -0.25, 1.0)?;
+        }
+    }
+    if let Some(t) = &filters.timescale {
+        check_positive("timescale speed", t.speed)?;
+        check_positive("timescale pitch", t.pitch)?;
+        check_positive("timescale rate", t.rate)?;
+    }
+    if let Some(t) = &filters.tremolo {
+        check_positive("tremolo frequency", t.frequency)?;
+        check_depth("tremolo depth", t.depth)?;
+    }
+    if let Some(v) = &filters.vibrato {
+        if let Some(freq) = v.frequency
+            && !(freq > 0.0 && freq <= 14.0)
+        {
+            bail!("vibrato frequency must be greater than 0 and at most 14 (got {})", freq);
+        }
+        check_depth("vibrato depth", v.depth)?;
+    }
+    if let Some(c) = &filters.channel_mix {
+        for (name, value) in [
+            ("channel mix leftToLeft", c.left_to_left),
+            ("channel mix leftToRight", c.left_to_right),
+            ("channel mix rightToLeft", c.right_to_left),
+            ("channel mix rightToRight", c.right_to_right),
+        ] {
+            if let Some(value) = value {
+                check_range(name, value, 0.0, 1.0)?;
+            }
+        }
+    }
+    if let Some(r) = &filters.rotation {
+        check_positive("rotation", r.rotation_hz)?;
+    }
+    if let Some(d) = &filters.distortion {
+        for (name, value) in [
+            ("distortion sinOffset", d.sin_offset),
+            ("distortion sinScale", d.sin_scale),
+            ("distortion cosOffset", d.cos_offset),
+            ("distortion cosScale", d.cos_scale),
+            ("distortion tanOffset", d.tan_offset),
+            ("distortion tanScale", d.tan_scale),
+            ("distortion offset", d.offset),
+            ("distortion scale", d.scale),
+        ] {
+            if let Some(value) = value
+                && !value.is_finite()
+            {
+                bail!("{} must be a finite number (got {})", name, value);
+            }
+        }
+    }
+    if let Some(smoothing) = filters.low_pass.as_ref().and_then(|l| l.smoothing)
+        && !(smoothing.is_finite() && smoothing > 1.0)
+    {
+        bail!("low-pass smoothing must be greater than 1.0 (got {})", smoothing);
+    }
+    if let Some(k) = &filters.karaoke {
+        for (name, value) in [("karaoke level", k.level), ("karaoke mono level", k.mono_level)] {
+            if let Some(value) = value {
+                check_range(name, value, 0.0, 1.0)?;
+            }
+        }
+        for (name, value) in [("karaoke filter band", k.filter_band), ("karaoke filter width", k.filter_width)] {
+            if let Some(value) = value
+                && !(value.is_finite() && value >= 0.0)
+            {
+                bail!("{} must not be negative (got {})", name, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_range(name: &str, value: f32, min: f32, max: f32) -> Result<()> {
+    if !(min..=max).contains(&value) {
+        bail!("{} must be between {} and {} (got {})", name, min, max, value);
+    }
+    Ok(())
+}
+
+/// Also rejects NaN and infinity, which `<=` comparisons let through.
+fn check_positive(name: &str, value: Option<f32>) -> Result<()> {
+    if let Some(value) = value
+        && !(value.is_finite() && value > 0.0)
+    {
+        bail!("{} must be greater than 0 (got {})", name, value);
+    }
+    Ok(())
+}
+
+fn check_depth(name: &str, value: Option<f32>) -> Result<()> {
+    if let Some(value) = value
+        && !(value > 0.0 && value <= 1.0)
+    {
+        bail!("{} must be greater than 0 and at most 1 (got {})", name, value);
+    }
+    Ok(())
+}
+
+/// Split a comma-separated list into exactly `N` floats.
+fn parse_floats<const N: usize>(s: &str, names: [&str; N]) -> Result<[f32; N], String> {
+    let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+    if parts.len() != N {
+        return Err(format!("expected {} comma-separated values ({})", N, names.join(",")));
+    }
+    let mut out = [0.0; N];
+    for (i, part) in parts.iter().enumerate() {
+        out[i] = part
+            .parse::<f32>()
+            .map_err(|_| format!("invalid {} value: {:?}", names[i], part))?;
+    }
+    Ok(out)
+}
+
+fn parse_eq_band(s: &str) -> Result<EqualizerBand, String> {
+    let (band, gain) = s
+        .split_once(':')
+        .ok_or_else(|| "expected BAND:GAIN, e.g. 0:0.25".to_string())?;
+    let band = band
+        .trim()
+        .parse::<i32>()
+        .map_err(|_| format!("invalid band: {:?}", band))?;
+    let gain = gain
+        .trim()
+        .parse::<f32>()
+        .map_err(|_| format!("invalid gain: {:?}", gain))?;
+    Ok(EqualizerBand { band, gain })
+}
+
+fn parse_timescale(s: &str) -> Result<TimescaleOptions, String> {
+    let [speed, pitch, rate] = parse_floats(s, ["speed", "pitch", "rate"])?;
+    Ok(TimescaleOptions { speed: Some(speed), pitch: Some(pitch), rate: Some(rate) })
+}
+
+fn parse_tremolo(s: &str) -> Result<TremoloOptions, String> {
+    let [frequency, depth] = parse_floats(s, ["frequency", "depth"])?;
+    Ok(TremoloOptions { frequency: Some(frequency), depth: Some(depth) })
+}
+
+fn parse_vibrato(s: &str) -> Result<VibratoOptions, String> {
+    let [frequency, depth] = parse_floats(s, ["frequency", "depth"])?;
+    Ok(VibratoOptions { frequency: Some(frequency), depth: Some(depth) })
+}
+
+fn parse_rotation(s: &str) -> Result<RotationOptions, String> {
+    let [hz] = parse_floats(s, ["hz"])?;
+    Ok(RotationOptions { rotation_hz: Some(hz) })
+}
+
+fn parse_distortion(s: &str) -> Result<DistortionOptions, String> {
+    let [sin_offset, sin_scale, cos_offset, cos_scale, tan_offset, tan_scale, offset, scale] = parse_floats(
+        s,
+        ["sinOffset", "sinScale", "cosOffset", "cosScale", "tanOffset", "tanScale", "offset", "scale"],
+    )?;
+    Ok(DistortionOptions {
+        sin_offset: Some(sin_offset),
+        sin_scale: Some(sin_scale),
+        cos_offset: Some(cos_offset),
+        cos_scale: Some(cos_scale),
+        tan_offset: Some(tan_offset),
+        tan_scale: Some(tan_scale),
+        offset: Some(offset),
+        scale: Some(scale),
+    })
+}
+
+fn parse_channel_mix(s: &str) -> Result<ChannelMixOptions, String> {
+    let [ll, lr, rl, rr] = parse_floats(s, ["leftToLeft", "leftToRight", "rightToLeft", "rightToRight"])?;
+    Ok(ChannelMixOptions {
+        left_to_left: Some(ll),
+        left_to_right: Some(lr),
+        right_to_left: Some(rl),
+        right_to_right: Some(rr),
+    })
+}
+
+fn parse_low_pass(s: &str) -> Result<LowPassOptions, String> {
+    let [smoothing] = parse_floats(s, ["smoothing"])?;
+    Ok(LowPassOptions { smoothing: Some(smoothing) })
+}
+
+fn parse_karaoke(s: &str) -> Result<KaraokeOptions, String> {
+    let [level, mono_level, filter_band, filter_width] =
+        parse_floats(s, ["level", "monoLevel", "filterBand", "filterWidth"])?;
+    Ok(KaraokeOptions {
+        level: Some(level),
+        mono_level: Some(mono_level),
+        filter_band: Some(filter_band),
+        filter_width: Some(filter_width),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn filters(value: serde_json::Value) -> AudioFilters {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn validates_ranges() {
+        let cases = [
+            (json!({}), true),
+            (json!({"volume": 1.0}), true),
+            (json!({"volume": 5.5}), false),
+            (json!({"equalizer": [{"band": 14, "gain": 0.25}]}), true),
+            (json!({"equalizer": [{"band": 15, "gain": 0.25}]}), false),
+            (json!({"equalizer": [{"band": 0, "gain": -0.5}]}), false),
+            (json!({"timescale": {"speed": 1.2}}), true),
+            (json!({"timescale": {"speed": 1.2, "pitch": 0.0}}), false),
+            (json!({"tremolo": {"frequency": 2.0, "depth": 0.5}}), true),
+            (json!({"tremolo": {"depth": 1.5}}), false),
+            (json!({"vibrato": {"frequency": 14.0, "depth": 1.0}}), true),
+            (json!({"vibrato": {"frequency": 14.5}}), false),
+            (json!({"channelMix": {"leftToRight": 0.5}}), true),
+            (json!({"channelMix": {"leftToRight": 1.5}}), false),
+            (json!({"lowPass": {"smoothing": 20.0}}), true),
+            (json!({"lowPass": {"smoothing": 1.0}}), false),
+            (json!({"karaoke": {"level": 1.0, "filterBand": 220.0}}), true),
+            (json!({"karaoke": {"filterBand": -1.0}}), false),
+        ];
+        for (value, valid) in cases {
+            assert_eq!(validate(&filters(value.clone())).is_ok(), valid, "{}", value);
+        }
+    }
+
+    #[test]
+    fn renders_partial_presets() {
+        let file = PresetFile {
+            description: None,
+            filters: filters(json!({"timescale": {"speed": 1.2}, "karaoke": {"level": 0.5}})),
+        };
+        let toml = render_preset(&file, PresetFormat::Toml).unwrap();
+        assert_eq!(toml, "[filters.karaoke]\nlevel = 0.5\n\n[filters.timescale]\nspeed = 1.2\n");
+        let json = render_preset(&file, PresetFormat::Json).unwrap();
+        assert!(!json.contains("null"), "{}", json);
+        let parsed: PresetFile = toml::from_str(&toml).unwrap();
+        assert_eq!(parsed.filters.timescale.and_then(|t| t.speed), Some(1.2));
+    }
+
+    #[test]
+    fn merges_over_active_filters() {
+        let mut active = filters(json!({
+            "timescale": {"speed": 1.1},
+            "equalizer": [{"band": 0, "gain": 0.1}, {"band": 1, "gain": 0.2}],
+        }));
+        let overlay = filters(json!({
+            "volume": 0.8,
+            "equalizer": [{"band": 2, "gain": 0.4}, {"band": 1, "gain": 0.3}],
+            "rotation": {"rotationHz": 0.2},
+        }));
+        merge(&mut active, &overlay);
+        // Through text so the f32 values aren't widened
+        let merged: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&active).unwrap()).unwrap();
+        assert_eq!(
+            merged,
+            json!({
+                "volume": 0.8,
+                "equalizer": [
+                    {"band": 0, "gain": 0.1},
+                    {"band": 1, "gain": 0.3},
+                    {"band": 2, "gain": 0.4},
+                ],
+                "timescale": {"speed": 1.1},
+                "rotation": {"rotationHz": 0.2},
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_non_finite_values() {
+        for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            let timescale = AudioFilters {
+                timescale: Some(TimescaleOptions {
+                    speed: Some(value),
+                    pitch: None,
+                    rate: None,
+                }),
+                ..Default::default()
+            };
+            let vibrato = AudioFilters {
+                vibrato: Some(VibratoOptions {
+                    frequency: Some(value),
+                    depth: None,
+                }),
+                ..Default::default()
+            };
+            let tremolo = AudioFilters {
+                tremolo: Some(TremoloOptions {
+                    frequency: None,
+                    depth: Some(value),
+                }),
+                ..Default::default()
+            };
+            let low_pass = AudioFilters {
+                low_pass: Some(LowPassOptions {
+                    smoothing: Some(value),
+                }),
+                ..Default::default()
+            };
+            let rotation = AudioFilters {
+                rotation: Some(RotationOptions {
+                    rotation_hz: Some(value),
+                }),
+                ..Default::default()
+            };
+            let distortion = AudioFilters {
+                distortion: Some(DistortionOptions {
+                    sin_offset: None,
+                    sin_scale: None,
+                    cos_offset: None,
+                    cos_scale: None,
+                    tan_offset: None,
+                    tan_scale: Some(value),
+                    offset: None,
+                    scale: None,
+                }),
+                ..Default::default()
+            };
+            for f in [timescale, vibrato, tremolo, low_pass, rotation, distortion] {
+                assert!(validate(&f).is_err(), "{:?}", f);
+            }
+        }
+        let backwards = AudioFilters {
+            rotation: Some(RotationOptions {
+                rotation_hz: Some(-5.0),
+            }),
+            ..Default::default()
+        };
+        assert!(validate(&backwards).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! `--follow` for `now-playing` and `queue`: print once, then again whenever
+//! the WebSocket reports a change, instead of polling `/webhook/audio`.
+
+use crate::api::{self, fetch_audio};
+use crate::ws::{self, Update};
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use colored::Colorize;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::io::IsTerminal;
+
+/// Output of the commands that stream updates.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Default)]
+pub enum Output {
+    /// Human-readable output
+    #[default]
+    Text,
+    /// One JSON object per line
+    Ndjson,
+}
+
+/// Events that can change what `now-playing` or `queue` show.
+const TRIGGERS: &[&str] = &["state_update", "initial_state", "track_start", "queue_update"];
+
+/// Fields that change on every tick without anything worth re-rendering.
+const VOLATILE: &[&str] = &["elapsedMs", "elapsed_ms", "position", "spectrogram", "timestamp"];
+
+pub async fn run<T: Serialize>(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    guild_id: Option<String>,
+    payload: &T,
+    output: Output,
+) -> Result<()> {
+    if api::dry_run().is_some() {
+        bail!("--follow cannot be combined with --dry-run");
+    }
+    let token = token.context("--follow needs a login; run `jorik auth login` first")?;
+
+    let json = fetch_audio(client, base_url, Some(token), payload).await?;
+    let guild_id = guild_id
+        .or_else(|| ws::guild_from_response(&json))
+        .context("could not determine the guild to follow; pass --guild-id")?;
+    let mut last = stable(&json);
+    render(&json, "initial", output);
+
+    let mut updates = ws::subscribe(base_url.to_string(), token.to_string(), guild_id.clone());
+    // Last state_update payload, to skip ticks that only move the clock
+    let mut last_state: Option<Value> = None;
+    let mut reconnecting = false;
+    while let Some(update) = updates.recv().await {
+        let event_type = match update {
+            Update::Connected if reconnecting => {
+                reconnecting = false;
+                eprintln!("{} Reconnected", "🔌".green());
+                // Anything may have happened while we were away
+                "reconnect".to_string()
+            }
+            Update::Connected => continue,
+            Update::Disconnected(reason) => {
+                reconnecting = true;
+                eprintln!(
+                    "{} WebSocket disconnected ({}), retrying in {}s",
+                    "⚠️".yellow(),
+                    reason,
+                    ws::RECONNECT_DELAY.as_secs()
+                );
+                continue;
+            }
+            Update::Event { event, .. } => {
+                if !TRIGGERS.contains(&event.event_type.as_str())
+                    || event.guild_id.as_deref().is_some_and(|g| g != guild_id)
+                {
+                    continue;
+                }
+                if matches!(event.event_type.as_str(), "state_update" | "initial_state") {
+                    let state = event.data.as_ref().map(stable);
+                    if state.is_some() && state == last_state {
+                        continue;
+                    }
+                    last_state = state;
+                }
+                event.event_type
+            }
+        };
+
+        let json = match fetch_audio(client, base_url, Some(token), payload).await {
+            Ok(json) => json,
+            Err(e) => {
+                eprintln!("{} {:#}", "✘".red(), e);
+                continue;
+            }
+        };
+        let current = stable(&json);
+        if current != last {
+            last = current;
+            render(&json, &event_type, output);
+        }
+    }
+    Ok(())
+}
+
+fn render(json: &Value, event_type: &str, output: Output) {
+    match output {
+        Output::Ndjson => {
+            let line = json!({
+                "at": chrono::Local::now().to_rfc3339(),
+                "event": event_type,
+                "data": json,
+            });
+            println!("{}", line);
+        }
+        Output::Text => {
+            if std::io::stdout().is_terminal() {
+                // Redraw in place, like `watch`
+                print!("\x1b[2J\x1b[H");
+            } else {
+                println!();
+            }
+            let summary = crate::summarize(json).unwrap_or_else(|| json.to_string());
+            println!("{}", summary);
+            println!(
+                "{}",
+                format!(
+                    "{} · {} · Ctrl+C to stop",
+                    chrono::Local::now().format("%H:%M:%S"),
+                    event_type
+                )
+                .dimmed()
+            );
+        }
+    }
+}
+
+/// `value` without the fields in [`VOLATILE`], for change detection.
+pub fn stable(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(k, _)| !VOLATILE.contains(&k.as_str()))
+                .map(|(k, v)| (k.clone(), stable(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(stable).collect()),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_volatile_fields() {
+        let at = |elapsed: u64, title: &str| {
+            json!({
+                "now_playing": {
+                    "track": {"title": title},
+                    "elapsedMs": elapsed,
+                    "spectrogram": [elapsed],
+                },
+                "upcoming": [{"title": "B", "position": elapsed}],
+            })
+        };
+        assert_eq!(stable(&at(1000, "A")), stable(&at(2000, "A")));
+        assert_ne!(stable(&at(1000, "A")), stable(&at(1000, "C")));
+        assert_eq!(
+            stable(&at(1000, "A")),
+            json!({"now_playing": {"track": {"title": "A"}}, "upcoming": [{"title": "B"}]})
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! Local playback history, stored as JSON lines under the XDG data dir.
+//!
+//! Entries are appended from successful `play`/`skip` responses and from
+//! `track_start`/`track_end` WS events. Recording is best effort: a history
+//! write never fails the command that triggered it.
+
+use crate::api::history_file_path;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryEvent {
+    Queued,
+    Started,
+    Finished,
+    Skipped,
+}
+
+impl HistoryEvent {
+    pub fn label(self) -> &'static str {
+        match self {
+            HistoryEvent::Queued => "queued",
+            HistoryEvent::Started => "started",
+            HistoryEvent::Finished => "finished",
+            HistoryEvent::Skipped => "skipped",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    /// Unix timestamp in seconds
+    pub at: i64,
+    pub event: HistoryEvent,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub author: String,
+    pub uri: String,
+    #[serde(default)]
+    pub duration_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requested_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guild_id: Option<String>,
+}
+
+impl HistoryEntry {
+    fn from_track(event: HistoryEvent, track: &Value, guild_id: Option<&str>) -> Option<Self> {
+        let track = track.get("track").unwrap_or(track);
+        let uri = track.get("uri").and_then(|v| v.as_str())?;
+        let field = |k: &str| {
+            track
+                .get(k)
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let duration_ms = ["duration", "durationMs", "length"]
+            .iter()
+            .find_map(|k| track.get(*k).and_then(|v| v.as_u64()))
+            .unwrap_or(0);
+        let requested_by = ["requested_by", "requester", "requestedBy"]
+            .iter()
+            .find_map(|k| track.get(*k).and_then(|v| v.as_str()))
+            .map(str::to_string);
+        Some(HistoryEntry {
+            at: chrono::Local::now().timestamp(),
+            event,
+            title: field("title"),
+            author: field("author"),
+            uri: uri.to_string(),
+            duration_ms,
+            requested_by,
+            guild_id: guild_id.map(str::to_string),
+        })
+    }
+
+    pub fn display_title(&self) -> String {
+        if self.author.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{} by {}", self.title, self.author)
+        }
+    }
+}
+
+fn append(entries: &[HistoryEntry]) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let path = history_file_path().context("could not determine data dir")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    for entry in entries {
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    }
+    Ok(())
+}
+
+/// Record the tracks of a successful `play` or `skip` response.
+pub fn record_response(json: &Value) {
+    let guild_id = json.get("guild_id").and_then(|v| v.as_str());
+    let entries: Vec<HistoryEntry> = match json.get("action").and_then(|v| v.as_str()) {
+        Some("play") => json
+            .get("tracks")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|t| HistoryEntry::from_track(HistoryEvent::Queued, t, guild_id))
+            .collect(),
+        Some("skip") => json
+            .get("skipped")
+            .and_then(|t| HistoryEntry::from_track(HistoryEvent::Skipped, t, guild_id))
+            .into_iter()
+            .collect(),
+        _ => Vec::new(),
+    };
+    let _ = append(&entries);
+}
+
+/// Events this close to an identical one are the same event seen by another
+/// subscribed process (the TUI, the daemon, `status --follow`, ...).
+const DUPLICATE_WINDOW_SECS: i64 = 5;
+
+/// Record a `track_start` / `track_end` WS event.
+pub fn record_event(event_type: &str, data: Option<&Value>, guild_id: Option<&str>) {
+    let event = match event_type {
+        "track_start" => HistoryEvent::Started,
+        "track_end" => HistoryEvent::Finished,
+        _ => return,
+    };
+    let Some(entry) = data.and_then(|d| HistoryEntry::from_track(event, d, guild_id)) else {
+        return;
+    };
+    if !is_duplicate(&entry, &recent_entries()) {
+        let _ = append(&[entry]);
+    }
+}
+
+/// Whether one of the `recent` entries (oldest first) within the window is the
+/// same event. Several of them are checked since two processes can append
+/// their `track_end` and `track_start` interleaved.
+fn is_duplicate(entry: &HistoryEntry, recent: &[HistoryEntry]) -> bool {
+    recent
+        .iter()
+        .rev()
+        .take_while(|e| (entry.at - e.at).abs() <= DUPLICATE_WINDOW_SECS)
+        .any(|e| e.event == entry.event && e.uri == entry.uri && e.guild_id == entry.guild_id)
+}
+
+/// How much of the end of the file [`recent_entries`] reads.
+const TAIL_BYTES: u64 = 4096;
+
+/// The newest entries, oldest first, reading only the end of the file.
+fn recent_entries() -> Vec<HistoryEntry> {
+    let read = || -> Option<(Vec<u8>, bool)> {
+        let mut file = fs::File::open(history_file_path()?).ok()?;
+        let start = file.metadata().ok()?.len().saturating_sub(TAIL_BYTES);
+        file.seek(SeekFrom::Start(start)).ok()?;
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail).ok()?;
+        Some((tail, start > 0))
+    };
+    read()
+        .map(|(tail, partial)| parse_tail(&tail, partial))
+        .unwrap_or_default()
+}
+
+/// Parse the end of the history file. A `partial` tail starts mid-line,
+/// possibly mid-character, so its first line is dropped.
+fn parse_tail(tail: &[u8], partial: bool) -> Vec<HistoryEntry> {
+    let tail = String::from_utf8_lossy(tail);
+    tail.lines()
+        .skip(usize::from(partial))
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect()
+}
+
+/// Load all history entries, oldest first. Unreadable lines are skipped.
+pub fn load() -> Vec<HistoryEntry> {
+    history_file_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|l| serde_json::from_str(l).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Entries newest first, optionally limited to the last `since`, to one guild
+/// and to entries whose title, author or URI contain `grep` (case-insensitive).
+pub fn query(
+    since: Option<Duration>,
+    grep: Option<&str>,
+    guild_id: Option<&str>,
+) -> Vec<HistoryEntry> {
+    let cutoff = since.map(|d| chrono::Local::now().timestamp() - d.as_secs() as i64);
+    let needle = grep.map(str::to_lowercase);
+    let mut entries: Vec<HistoryEntry> = load()
+        .into_iter()
+        .filter(|e| cutoff.is_none_or(|c| e.at >= c))
+        .filter(|e| guild_id.is_none_or(|g| e.guild_id.as_deref() == Some(g)))
+        .filter(|e| {
+            needle.as_ref().is_none_or(|n| {
+                [&e.title, &e.author, &e.uri]
+                    .iter()
+                    .any(|f| f.to_lowercase().contains(n))
+            })
+        })
+        .collect();
+    entries.reverse();
+    entries
+}
+
+/// The most recent track that stopped playing: the last finished or skipped
+/// entry, or failing that the track that started before the current one.
+pub fn last_finished() -> Option<HistoryEntry> {
+    let entries = load();
+    entries
+        .iter()
+        .rev()
+        .find(|e| matches!(e.event, HistoryEvent::Finished | HistoryEvent::Skipped))
+        .or_else(|| {
+            entries
+                .iter()
+                .rev()
+                .filter(|e| e.event == HistoryEvent::Started)
+                .nth(1)
+        })
+        .cloned()
+}
+
+/// Guild IDs seen in the history, most recently used first.
+pub fn guild_ids() -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+    for entry in load().into_iter().rev() {
+        if let Some(id) = entry.guild_id
+            && !ids.contains(&id)
+        {
+            ids.push(id);
+        }
+    }
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(at: i64, event: HistoryEvent, uri: &str) -> HistoryEntry {
+        HistoryEntry {
+            at,
+            event,
+            title: "Зірка".to_string(),
+            author: String::new(),
+            uri: uri.to_string(),
+            duration_ms: 0,
+            requested_by: None,
+            guild_id: Some("42".to_string()),
+        }
+    }
+
+    #[test]
+    fn parses_a_tail_cut_mid_character() {
+        let entries = [
+            entry(1, HistoryEvent::Started, "a"),
+            entry(2, HistoryEvent::Finished, "a"),
+        ];
+        let lines: String = entries
+            .iter()
+            .map(|e| serde_json::to_string(e).unwrap() + "\n")
+            .collect();
+        // Cut inside the first title's first (two-byte) character
+        let cut = lines.find("Зірка").unwrap() + 1;
+        let parsed = parse_tail(&lines.as_bytes()[cut..], true);
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].event, HistoryEvent::Finished);
+        assert_eq!(parse_tail(lines.as_bytes(), false).len(), 2);
+    }
+
+    #[test]
+    fn detects_duplicates_within_the_window() {
+        // Another process appended the same end and start, interleaved with ours
+        let recent = [
+            entry(100, HistoryEvent::Finished, "a"),
+            entry(100, HistoryEvent::Started, "b"),
+        ];
+        let cases = [
+            (entry(101, HistoryEvent::Finished, "a"), true),
+            (entry(101, HistoryEvent::Started, "b"), true),
+            (entry(101, HistoryEvent::Started, "a"), false),
+            (entry(100 + DUPLICATE_WINDOW_SECS + 1, HistoryEvent::Started, "b"), false),
+        ];
+        for (e, expected) in cases {
+            assert_eq!(is_duplicate(&e, &recent), expected, "{:?}", e);
+        }
+    }
+}
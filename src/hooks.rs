@@ -0,0 +1,453 @@
+//! Event hooks: shell commands run when WebSocket events arrive.
+//!
+//! Hooks live in `settings.json` under `hooks`:
+//!
+//! ```json
+//! "hooks": [
+//!   { "on": ["track_start"], "run": "notify-send \"$JORIK_DATA_TRACK_TITLE\"" },
+//!   { "name": "dim", "on": ["track_start"], "when": { "data.track.author": { "contains": "ambient" } },
+//!     "run": "hue-dim 30", "timeout_secs": 5 }
+//! ]
+//! ```
+//!
+//! A command gets the event as JSON on stdin and its scalar fields in the
+//! environment: `JORIK_EVENT` is the type and `data.track.title` becomes
+//! `JORIK_DATA_TRACK_TITLE`. Hooks run from `jorik daemon` and from
+//! `jorik events --hooks`; at most `hook_concurrency` commands run at once
+//! and the rest wait their turn.
+
+use crate::api::{self, Settings};
+use colored::Colorize;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+
+/// Killed after this long unless the hook sets `timeout_secs`.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Sent several times a second; `*` leaves them out unless named.
+const NOISY: &[&str] = &["spectrogram_update"];
+
+/// Fields nested deeper than this are left out of the environment.
+const MAX_ENV_DEPTH: usize = 4;
+
+/// Longer values are only on stdin: the environment has a size limit and a
+/// command doesn't start when it is exceeded.
+const MAX_ENV_VALUE: usize = 4096;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Hook {
+    /// Shown in the log; defaults to the command
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Event types to run on, `*` for all
+    pub on: Vec<String>,
+    /// Conditions on event fields (dotted paths) that all have to hold
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub when: BTreeMap<String, Condition>,
+    /// Run with `sh -c` (`cmd /C` on Windows)
+    pub run: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+/// A value the field has to equal, or an object of tests. Objects are
+/// always tests, so a misspelt operator fails to load instead of becoming an
+/// equality check that never holds; compare to an object with `{"eq": ...}`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Condition {
+    Test(Test),
+    Equals(Value),
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::Object(map) => Test::deserialize(Value::Object(map))
+                .map(Condition::Test)
+                .map_err(D::Error::custom),
+            other => Ok(Condition::Equals(other)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Test {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eq: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ne: Option<Value>,
+    /// Case-insensitive substring of the field as text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contains: Option<String>,
+    /// The field equals one of these
+    #[serde(default, rename = "in", skip_serializing_if = "Option::is_none")]
+    pub any_of: Option<Vec<Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exists: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gt: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lt: Option<f64>,
+}
+
+impl Condition {
+    fn holds(&self, field: Option<&Value>) -> bool {
+        match self {
+            Condition::Equals(expected) => field.is_some_and(|v| equals(v, expected)),
+            Condition::Test(test) => test.holds(field),
+        }
+    }
+}
+
+impl Test {
+    fn holds(&self, field: Option<&Value>) -> bool {
+        if let Some(exists) = self.exists
+            && exists != field.is_some_and(|v| !v.is_null())
+        {
+            return false;
+        }
+        let number = field.and_then(Value::as_f64);
+        self.eq.as_ref().is_none_or(|e| field.is_some_and(|v| equals(v, e)))
+            && self.ne.as_ref().is_none_or(|e| !field.is_some_and(|v| equals(v, e)))
+            && self.contains.as_ref().is_none_or(|needle| {
+                field.is_some_and(|v| text(v).to_lowercase().contains(&needle.to_lowercase()))
+            })
+            && self
+                .any_of
+                .as_ref()
+                .is_none_or(|options| field.is_some_and(|v| options.iter().any(|e| equals(v, e))))
+            && self.gt.is_none_or(|limit| number.is_some_and(|n| n > limit))
+            && self.lt.is_none_or(|limit| number.is_some_and(|n| n < limit))
+    }
+}
+
+/// Equal as JSON, or as text so `"42"` matches the number 42.
+fn equals(value: &Value, expected: &Value) -> bool {
+    value == expected || text(value) == text(expected)
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// The field at a dotted path such as `data.track.title`; numeric segments
+/// index arrays.
+fn lookup<'a>(event: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(event, |value, key| match value {
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => value.get(key),
+    })
+}
+
+impl Hook {
+    fn label(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            let mut label: String = self.run.chars().take(40).collect();
+            if label.len() < self.run.len() {
+                label.push('…');
+            }
+            label
+        })
+    }
+
+    fn matches(&self, event_type: &str, event: &Value) -> bool {
+        let on = self
+            .on
+            .iter()
+            .any(|t| t == event_type || (t == "*" && !NOISY.contains(&event_type)));
+        on && self.when.iter().all(|(path, c)| c.holds(lookup(event, path)))
+    }
+}
+
+/// Runs the configured hooks for events, sharing one concurrency limit.
+#[derive(Clone)]
+pub struct Runner {
+    hooks: Arc<Vec<Hook>>,
+    permits: Arc<Semaphore>,
+}
+
+impl Runner {
+    /// `None` when no hooks are configured.
+    pub fn from_settings(settings: &Settings) -> Option<Self> {
+        if settings.hooks.is_empty() {
+            return None;
+        }
+        Some(Runner {
+            hooks: Arc::new(settings.hooks.clone()),
+            permits: Arc::new(Semaphore::new(settings.hook_concurrency.max(1))),
+        })
+    }
+
+    pub fn count(&self) -> usize {
+        self.hooks.len()
+    }
+
+    /// Start every hook that matches `event` in the background.
+    pub fn dispatch(&self, event_type: &str, event: &Value) {
+        for hook in self.hooks.iter().filter(|h| h.matches(event_type, event)) {
+            if api::dry_run().is_some() {
+                eprintln!(
+                    "{} Would run hook {} for {}",
+                    "🪝".cyan(),
+                    hook.label().bold(),
+                    event_type
+                );
+                continue;
+            }
+            let hook = hook.clone();
+            let event_type = event_type.to_string();
+            let event = event.clone();
+            let permits = self.permits.clone();
+            tokio::spawn(async move {
+                let Ok(_permit) = permits.acquire_owned().await else {
+                    return;
+                };
+                run(&hook, &event_type, &event).await;
+            });
+        }
+    }
+}
+
+async fn run(hook: &Hook, event_type: &str, event: &Value) {
+    let label = hook.label();
+    let mut command = shell(&hook.run);
+    command
+        .env("JORIK_EVENT", event_type)
+        .env("JORIK_HOOK", &label)
+        .envs(env_vars(event))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Dropping the child on timeout ends it
+        .kill_on_drop(true);
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("{} Hook {} could not start: {}", "✘".red(), label.bold(), e);
+            return;
+        }
+    };
+    let stdin = child.stdin.take();
+    let input = event.to_string();
+    // Fed while the output is read and under the timeout, so a hook that
+    // never reads stdin or fills stdout first can't block for good
+    let feed = async move {
+        if let Some(mut stdin) = stdin {
+            // Hooks that don't read stdin close it early; that's fine
+            let _ = stdin.write_all(input.as_bytes()).await;
+        }
+    };
+    let finish = async { tokio::join!(feed, child.wait_with_output()).1 };
+
+    let limit = hook.timeout_secs.map(Duration::from_secs).unwrap_or(DEFAULT_TIMEOUT);
+    let output = match tokio::time::timeout(limit, finish).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            eprintln!("{} Hook {} failed: {}", "✘".red(), label.bold(), e);
+            return;
+        }
+        Err(_) => {
+            eprintln!(
+                "{} Hook {} killed after {}s",
+                "⚠️".yellow(),
+                label.bold(),
+                limit.as_secs()
+            );
+            return;
+        }
+    };
+    // Output goes to stderr so it never mixes with `jorik events` output
+    for line in String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .chain(String::from_utf8_lossy(&output.stderr).lines())
+    {
+        eprintln!("{} {}", format!("[{}]", label).dimmed(), line);
+    }
+    if !output.status.success() {
+        let code = output
+            .status
+            .code()
+            .map_or("a signal".to_string(), |c| c.to_string());
+        eprintln!("{} Hook {} exited with {}", "✘".red(), label.bold(), code);
+    }
+}
+
+#[cfg(unix)]
+fn shell(script: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(script);
+    command
+}
+
+#[cfg(windows)]
+fn shell(script: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(script);
+    command
+}
+
+/// `JORIK_<PATH>` for every scalar field of the event that isn't too long.
+fn env_vars(event: &Value) -> Vec<(String, String)> {
+    let mut vars = Vec::new();
+    collect_env(event, "JORIK", 0, &mut vars);
+    vars
+}
+
+fn collect_env(value: &Value, prefix: &str, depth: usize, vars: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) if depth < MAX_ENV_DEPTH => {
+            for (key, value) in map {
+                collect_env(value, &format!("{}_{}", prefix, env_name(key)), depth + 1, vars);
+            }
+        }
+        Value::Object(_) | Value::Array(_) | Value::Null => {}
+        scalar => {
+            let value = text(scalar);
+            if value.len() <= MAX_ENV_VALUE {
+                vars.push((prefix.to_string(), value));
+            }
+        }
+    }
+}
+
+/// `guildId` → `GUILD_ID`, `artwork-url` → `ARTWORK_URL`.
+fn env_name(key: &str) -> String {
+    let mut name = String::new();
+    let mut prev_lower = false;
+    for c in key.chars() {
+        if c.is_ascii_alphanumeric() {
+            if c.is_ascii_uppercase() && prev_lower {
+                name.push('_');
+            }
+            name.push(c.to_ascii_uppercase());
+            prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        } else {
+            if !name.ends_with('_') {
+                name.push('_');
+            }
+            prev_lower = false;
+        }
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn hook(value: Value) -> Hook {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn matches_events() {
+        let event = json!({
+            "type": "track_start",
+            "guildId": "42",
+            "data": {"track": {"title": "Ambient Dawn", "author": "X", "durationMs": 200000}},
+            "tracks": [{"uri": "a"}],
+        });
+        let on_cases = [
+            (json!(["track_start"]), true),
+            (json!(["track_end"]), false),
+            (json!(["queue_update", "track_start"]), true),
+            (json!(["*"]), true),
+        ];
+        for (on, matches) in on_cases {
+            let hook = hook(json!({"on": on, "run": "true"}));
+            assert_eq!(hook.matches("track_start", &event), matches, "{}", on);
+        }
+        let cases = [
+            (json!({"guildId": 42}), true),
+            (json!({"guildId": "7"}), false),
+            (json!({"data.track.title": {"contains": "AMBIENT"}}), true),
+            (json!({"data.track.durationMs": {"gt": 180000}}), true),
+            (json!({"data.track.durationMs": {"lt": 180000}}), false),
+            (json!({"data.track.author": {"in": ["X", "Y"]}}), true),
+            (json!({"data.track.author": {"ne": "X"}}), false),
+            (json!({"data.track.author": {"eq": "X", "exists": true}}), true),
+            (json!({"data.requester": {"exists": false}}), true),
+            (json!({"data.missing": "x"}), false),
+            (json!({"tracks.0.uri": "a"}), true),
+            (json!({"guildId": "42", "data.track.author": "Y"}), false),
+        ];
+        for (when, matches) in cases {
+            let hook = hook(json!({"on": ["track_start"], "when": when, "run": "true"}));
+            assert_eq!(hook.matches("track_start", &event), matches, "{}", when);
+        }
+    }
+
+    #[test]
+    fn wildcard_skips_noisy_events() {
+        let event = json!({"type": "spectrogram_update"});
+        assert!(!hook(json!({"on": ["*"], "run": "true"})).matches("spectrogram_update", &event));
+        assert!(
+            hook(json!({"on": ["spectrogram_update"], "run": "true"}))
+                .matches("spectrogram_update", &event)
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_operators() {
+        let err = serde_json::from_value::<Hook>(json!({
+            "on": ["track_start"],
+            "when": {"data.track.author": {"contans": "ambient"}},
+            "run": "true",
+        }))
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown field `contans`"), "{}", err);
+    }
+
+    #[test]
+    fn names_env_vars() {
+        let cases = [
+            ("type", "TYPE"),
+            ("guildId", "GUILD_ID"),
+            ("elapsedMs", "ELAPSED_MS"),
+            ("artwork-url", "ARTWORK_URL"),
+            ("requested_by", "REQUESTED_BY"),
+            ("URI", "URI"),
+            ("mp3Url", "MP3_URL"),
+        ];
+        for (key, expected) in cases {
+            assert_eq!(env_name(key), expected, "{}", key);
+        }
+    }
+
+    #[test]
+    fn flattens_scalars_into_env() {
+        let event = json!({
+            "type": "track_start",
+            "data": {
+                "track": {"title": "Song", "durationMs": 1000, "paused": false, "tags": ["a"]},
+            },
+            "lyrics": "x".repeat(MAX_ENV_VALUE + 1),
+            "empty": null,
+        });
+        let mut vars = env_vars(&event);
+        vars.sort();
+        let expected = [
+            ("JORIK_DATA_TRACK_DURATION_MS", "1000"),
+            ("JORIK_DATA_TRACK_PAUSED", "false"),
+            ("JORIK_DATA_TRACK_TITLE", "Song"),
+            ("JORIK_TYPE", "track_start"),
+        ];
+        let expected: Vec<(String, String)> =
+            expected.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        assert_eq!(vars, expected);
+    }
+}
@@ -1,23 +1,49 @@
 use anyhow::{Context, Result, bail};
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
 use colored::Colorize;
+use colored_json::ToColoredJson;
 use dirs::config_dir;
+use follow::Output;
 use open::that;
 use reqwest::{Client, Url};
 use semver::Version;
 use serde_json::Value;
 use std::fs::{self, File};
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
 use std::process::Command;
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
 use tokio::time::timeout;
 
+mod aliases;
 mod api;
 mod ascii;
+mod complete;
+mod daemon;
+mod events;
+mod favorites;
+mod filters;
+mod follow;
+mod history;
+mod hooks;
 mod image;
+#[cfg(target_os = "linux")]
+mod mpris;
+mod normalize;
+mod playlist;
+mod queue;
+mod script;
+mod search;
+mod shell;
+mod stats;
+mod status;
 mod tui;
+mod undo;
+mod upload;
+mod wait;
+mod ws;
 
 use api::*;
 
@@ -38,6 +64,43 @@ struct Cli {
     #[arg(long, global = true, env = "JORIK_TOKEN")]
     token: Option<String>,
 
+    /// Profile whose local data (favorites, ...) to use
+    #[arg(long, global = true, env = "JORIK_PROFILE", default_value = "default")]
+    profile: String,
+
+    /// Send links as pasted instead of normalising them (tracking params, hosts, app URIs)
+    #[arg(
+        long,
+        global = true,
+        env = "JORIK_NO_NORMALIZE",
+        value_parser = clap::builder::BoolishValueParser::new()
+    )]
+    no_normalize: bool,
+
+    /// Don't ask before stopping or clearing a long queue
+    #[arg(long, short = 'y', global = true)]
+    yes: bool,
+
+    /// Print requests instead of sending them (`--dry-run=curl` for curl lines)
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "json"
+    )]
+    dry_run: Option<DryRun>,
+
+    /// Talk to the server directly even when `jorik daemon` is running
+    #[arg(
+        long,
+        global = true,
+        env = "JORIK_NO_DAEMON",
+        value_parser = clap::builder::BoolishValueParser::new()
+    )]
+    no_daemon: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -48,9 +111,24 @@ enum Commands {
     Health,
     /// Enqueue audio to play
     Play {
-        /// Query/URL to play
-        #[arg(num_args = 1..)]
+        /// Query/URL to play, a local audio file to upload, `-` to read a
+        /// playlist from stdin, or `fav:<name|tag>` to play favorites
+        #[arg(num_args = 1.., required_unless_present = "file")]
         query: Vec<String>,
+        /// Playlist file to enqueue (M3U, PLS, JSON array or one entry per line)
+        #[arg(long, short = 'f', conflicts_with = "query")]
+        file: Option<PathBuf>,
+        /// Maximum number of playlist entries enqueued at once.
+        /// Values above 1 are faster but may queue entries out of order.
+        #[arg(long, default_value = "1")]
+        concurrency: usize,
+        /// Shuffle favorites selected by tag (`fav:<tag>`)
+        #[arg(long)]
+        shuffle: bool,
+        /// Search source for plain-text queries (a `yt:`/`sc:`/`sp:`/`am:`
+        /// prefix in the query wins; defaults to the profile's source)
+        #[arg(long, value_enum)]
+        source: Option<search::SearchSource>,
         /// Guild ID (optional)
         #[arg(long)]
         guild_id: Option<String>,
@@ -67,8 +145,24 @@ enum Commands {
         #[arg(long)]
         avatar_url: Option<String>,
     },
-    /// Enqueue the "turip" track (Spotify link)
-    Turip {
+    /// Search for tracks and optionally pick one to enqueue
+    Search {
+        /// Search query
+        #[arg(num_args = 1..)]
+        query: Vec<String>,
+        /// Where to search (a `yt:`/`sc:`/`sp:`/`am:` prefix in the query
+        /// wins; defaults to the profile's source, then YouTube)
+        #[arg(long, value_enum)]
+        source: Option<search::SearchSource>,
+        /// Maximum number of results
+        #[arg(long, default_value = "5")]
+        limit: usize,
+        /// Prompt for a result and enqueue it
+        #[arg(long)]
+        pick: bool,
+        /// Enqueue the Nth result without prompting (implies --pick)
+        #[arg(long, value_name = "N")]
+        select: Option<usize>,
         /// Guild ID (optional)
         #[arg(long)]
         guild_id: Option<String>,
@@ -85,6 +179,105 @@ enum Commands {
         #[arg(long)]
         avatar_url: Option<String>,
     },
+    /// Show locally recorded playback history (newest first)
+    History {
+        #[command(subcommand)]
+        command: Option<HistorySubcommand>,
+        /// Only show entries newer than this (e.g. 30m, 1h, 2days)
+        #[arg(long, value_parser = humantime::parse_duration)]
+        since: Option<Duration>,
+        /// Only show entries whose title, author or URI contain this text
+        #[arg(long)]
+        grep: Option<String>,
+        /// Maximum number of entries to show
+        #[arg(long, default_value = "20")]
+        limit: usize,
+    },
+    /// Per-profile settings
+    Profile {
+        #[command(subcommand)]
+        command: ProfileSubcommand,
+    },
+    /// Manage and play favorite tracks
+    Fav {
+        #[command(subcommand)]
+        command: FavSubcommand,
+    },
+    /// Listening statistics from the local history
+    Stats {
+        /// Only count entries newer than this (e.g. 7d, 24h)
+        #[arg(long, value_parser = humantime::parse_duration, default_value = "7d")]
+        since: Duration,
+        /// Only count entries from this guild
+        #[arg(long)]
+        guild: Option<String>,
+        /// Number of entries in each top list
+        #[arg(long, default_value = "5")]
+        top: usize,
+        #[arg(long, value_enum, default_value = "text")]
+        output: OutputFormat,
+    },
+    /// Print WebSocket events as they arrive
+    Events {
+        /// Only show these event types (comma-separated, e.g. state_update,track_start)
+        #[arg(long = "type", value_delimiter = ',')]
+        types: Vec<String>,
+        /// Guild to subscribe to (defaults to the one you are in)
+        #[arg(long)]
+        guild: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+        /// Timestamp events relative to the connection instead of the clock
+        #[arg(long)]
+        since_connect: bool,
+        /// Drop spectrogram_update events and spectrogram fields
+        #[arg(long)]
+        no_spectrogram: bool,
+        #[arg(long, value_enum, default_value_t = Output::Text)]
+        output: Output,
+        /// Run the hooks from settings.json for the events
+        #[arg(long)]
+        hooks: bool,
+    },
+    /// Run jorik commands from a script, one per line (`-` or no file for stdin)
+    Exec {
+        script: Option<PathBuf>,
+        /// Keep going after a command fails
+        #[arg(long)]
+        continue_on_error: bool,
+    },
+    /// Block until a playback condition is met (exits 124 on timeout)
+    Wait {
+        #[command(flatten)]
+        args: wait::WaitArgs,
+    },
+    /// Restore the queue as it was before the last stop, clear, skip or shuffle
+    Undo {
+        /// Voice channel ID (optional)
+        #[arg(long)]
+        channel_id: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+    },
+    /// Seek within the current track (e.g. 1:23, 83, 1m30s)
+    Seek {
+        #[arg(value_parser = parse_position)]
+        position: Duration,
+        #[arg(long)]
+        guild_id: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+    },
+    /// Re-enqueue the last finished track at the front of the queue
+    Previous {
+        #[arg(long)]
+        guild_id: Option<String>,
+        /// Voice channel ID (optional)
+        #[arg(long)]
+        channel_id: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+    },
     /// Skip the current track
     Skip {
         #[arg(long)]
@@ -108,6 +301,8 @@ enum Commands {
     },
     /// Show the current queue
     Queue {
+        #[command(subcommand)]
+        command: Option<QueueSubcommand>,
         #[arg(long)]
         guild_id: Option<String>,
         #[arg(long)]
@@ -116,6 +311,12 @@ enum Commands {
         limit: usize,
         #[arg(long, default_value = "0")]
         offset: usize,
+        /// Keep running and print again whenever playback changes
+        #[arg(long)]
+        follow: bool,
+        /// Output format for --follow
+        #[arg(long, value_enum, default_value_t = Output::Text, requires = "follow")]
+        output: Output,
     },
     /// Clear the queue
     Clear {
@@ -130,6 +331,17 @@ enum Commands {
         guild_id: Option<String>,
         #[arg(long)]
         user_id: Option<String>,
+        /// Keep running and print again whenever playback changes
+        #[arg(long)]
+        follow: bool,
+        /// Output format for --follow
+        #[arg(long, value_enum, default_value_t = Output::Text, requires = "follow")]
+        output: Output,
+    },
+    /// The current track for status bars (waybar, polybar, i3blocks, tmux)
+    Status {
+        #[command(flatten)]
+        args: status::StatusArgs,
     },
     /// Set loop mode (off, track, queue)
     Loop {
@@ -156,10 +368,12 @@ enum Commands {
         #[arg(long)]
         user_id: Option<String>,
     },
-    /// Apply audio filters (clear, bassboost, nightcore, vaporwave, 8d, soft, tremolo, vibrato, karaoke)
+    /// Apply audio filters (clear, bassboost, nightcore, vaporwave, 8d, soft, tremolo, vibrato, karaoke or a user preset)
     Filter {
-        /// Filter style
-        style: String,
+        /// Filter preset name
+        style: Option<String>,
+        #[command(subcommand)]
+        command: Option<FilterSubcommand>,
         #[arg(long)]
         guild_id: Option<String>,
         #[arg(long)]
@@ -177,6 +391,44 @@ enum Commands {
         #[arg(long)]
         user_id: Option<String>,
     },
+    /// Interactive prompt for running commands, with history and tab completion
+    Shell {
+        /// Guild to watch for track changes (defaults to the one you are in)
+        #[arg(long)]
+        guild_id: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+    },
+    /// Print a shell completion script (e.g. `source <(jorik completions bash)`)
+    Completions {
+        #[arg(value_enum)]
+        shell: complete::Shell,
+    },
+    /// Print the man page, or write one per subcommand into a directory
+    Man {
+        #[arg(long, short = 'o', value_name = "DIR")]
+        output: Option<PathBuf>,
+    },
+    /// Keep the WebSocket connection and playback state in the background so
+    /// other commands answer instantly
+    Daemon {
+        #[command(subcommand)]
+        command: Option<DaemonSubcommand>,
+        /// Subscribe to this guild right away (the one you are in when hooks
+        /// are configured)
+        #[arg(long)]
+        guild_id: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+    },
+    /// Show up as a media player on the D-Bus session bus (Linux) for media
+    /// keys, desktop widgets and playerctl
+    Mpris {
+        #[arg(long)]
+        guild_id: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+    },
     /// Launch the TUI interface
     Tui {
         #[arg(long)]
@@ -184,6 +436,192 @@ enum Commands {
         #[arg(long)]
         user_id: Option<String>,
     },
+    /// Run an alias or macro from settings (lists them without a name)
+    Run {
+        name: Option<String>,
+        /// Extra arguments passed to `play` for query aliases
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Aliases can also be invoked directly, e.g. `jorik turip`
+    #[command(external_subcommand)]
+    External(Vec<String>),
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+enum FilterSubcommand {
+    /// Build a filter set from individual parameters
+    Custom {
+        #[command(flatten)]
+        args: Box<filters::CustomFilterArgs>,
+        #[arg(long)]
+        guild_id: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+    },
+    /// Stack a preset on top of the currently active filters
+    Add {
+        preset: String,
+        #[arg(long)]
+        guild_id: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+    },
+    /// Remove one filter kind from the currently active filters
+    Remove {
+        #[arg(value_enum)]
+        kind: filters::FilterKind,
+        #[arg(long)]
+        guild_id: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+    },
+    /// Show the filters currently active on the server
+    Show {
+        #[arg(long)]
+        guild_id: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+    },
+    /// Manage saved filter presets
+    Preset {
+        #[command(subcommand)]
+        command: PresetSubcommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum QueueSubcommand {
+    /// Write the whole queue (current track included) to a playlist file
+    Export {
+        /// Output format (guessed from --output, defaults to json)
+        #[arg(long, value_enum)]
+        format: Option<queue::ExportFormat>,
+        /// Output file (stdout if omitted)
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+        #[arg(long)]
+        guild_id: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+    },
+    /// Re-enqueue the tracks of an exported queue, in order
+    Import {
+        file: PathBuf,
+        #[arg(long)]
+        guild_id: Option<String>,
+        /// Voice channel ID (optional)
+        #[arg(long)]
+        channel_id: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ProfileSubcommand {
+    /// Show or set the default search source for plain-text queries
+    Source {
+        #[arg(value_enum)]
+        source: Option<search::SearchSource>,
+        /// Go back to the server's default source
+        #[arg(long, conflicts_with = "source")]
+        clear: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum FavSubcommand {
+    /// Star the current track (`current`) or a query/URL
+    Add {
+        /// `current` or a query/URL
+        #[arg(num_args = 1.., required = true)]
+        target: Vec<String>,
+        /// Tag to attach (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Name to save the favorite under (defaults to the title or query)
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        guild_id: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+    },
+    /// List favorites
+    List {
+        /// Only list favorites with this tag
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// Enqueue a favorite by name, or every favorite with a tag
+    Play {
+        name_or_tag: String,
+        /// Shuffle the favorites of a tag before enqueueing
+        #[arg(long)]
+        shuffle: bool,
+        #[arg(long)]
+        guild_id: Option<String>,
+        /// Voice channel ID (optional)
+        #[arg(long)]
+        channel_id: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+    },
+    /// Remove a favorite
+    #[command(alias = "remove")]
+    Rm { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum HistorySubcommand {
+    /// Re-enqueue the Nth entry of `jorik history` (1 = newest)
+    Replay {
+        n: usize,
+        #[arg(long)]
+        guild_id: Option<String>,
+        /// Voice channel ID (optional)
+        #[arg(long)]
+        channel_id: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PresetSubcommand {
+    /// List built-in and user presets
+    List,
+    /// Show the filters of a preset
+    Show { name: String },
+    /// Save a user preset from filter parameters
+    Save {
+        name: String,
+        /// Short description shown in preset lists
+        #[arg(long)]
+        description: Option<String>,
+        /// File format of the saved preset
+        #[arg(long, value_enum, default_value = "toml")]
+        format: filters::PresetFormat,
+        #[command(flatten)]
+        args: Box<filters::CustomFilterArgs>,
+    },
+    /// Delete a user preset
+    Delete { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum DaemonSubcommand {
+    /// Show whether a daemon is running and what it holds
+    Status,
+    /// Stop the running daemon
+    Stop,
 }
 
 #[derive(Subcommand, Debug)]
@@ -249,8 +687,19 @@ pub async fn check_for_updates(client: &Client) -> Option<(String, Vec<GiteaAsse
     }
 }
 
+/// The clap definition as installed: named `jorik`, with values for
+/// presets, profiles, favorites and guilds filled in at completion time.
+fn cli_command() -> clap::Command {
+    complete::annotate(Cli::command().name("jorik").bin_name("jorik"))
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Answers completion requests from the scripts `jorik completions` prints
+    clap_complete::CompleteEnv::with_factory(cli_command)
+        .bin("jorik")
+        .complete();
+
     {
         let args: Vec<_> = std::env::args_os().collect();
         let mut want_version = false;
@@ -282,12 +731,27 @@ async fn main() -> Result<()> {
 
     let mut cli = Cli::parse();
     
-    let settings = api::load_settings();
+    let settings = match api::try_load_settings() {
+        Ok(settings) => settings,
+        // Running without the configured hooks would silently drop them
+        Err(e) if runs_hooks(&cli.command) => return Err(e),
+        Err(e) => {
+            eprintln!("{} {:#}, using the default settings", "⚠".yellow(), e);
+            Settings::default()
+        }
+    };
     
     if cli.base_url == "https://jorik.xserv.pp.ua" && settings.base_url != "https://jorik.xserv.pp.ua" {
         cli.base_url = settings.base_url.clone();
     }
     
+    api::validate_profile_name(&cli.profile)?;
+    normalize::set_enabled(!cli.no_normalize);
+    daemon::set_enabled(!cli.no_daemon);
+    if let Some(mode) = cli.dry_run {
+        api::set_dry_run(mode);
+    }
+
     let client = Client::builder()
         .user_agent("jorik-cli")
         .timeout(Duration::from_secs(10))
@@ -299,22 +763,94 @@ async fn main() -> Result<()> {
             settings,
             cli.token.or_else(load_token),
             guild_id,
-            user_id
+            user_id,
+            cli.profile.clone(),
         ).await? {
              return trigger_update(&client, &latest, &assets).await;
         }
         return Ok(());
     }
 
-    let update_client = client.clone();
-    let update_check = tokio::spawn(async move { check_for_updates(&update_client).await });
+    // A running daemon checks once for everyone. The prompt needs someone
+    // at a terminal to answer it and mustn't mix into piped output.
+    let check = io::stdin().is_terminal()
+        && io::stdout().is_terminal()
+        && offers_update(&cli.command)
+        && !daemon::is_running();
+    let update_check = check.then(|| {
+        let update_client = client.clone();
+        tokio::spawn(async move { check_for_updates(&update_client).await })
+    });
+
+    execute(cli, &client, &settings, 0).await?;
+
+    if let Some(update_check) = update_check
+        && let Ok(Some((latest, assets))) = update_check.await
+    {
+        println!(
+            "\n{} {} -> {}",
+            "A new version of jorik-cli is available:".yellow().bold(),
+            env!("CARGO_PKG_VERSION").red(),
+            latest.green().bold()
+        );
+
+        print!("Do you want to update and install the latest version? [y/N]: ");
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
 
+        if input.trim().eq_ignore_ascii_case("y") {
+            trigger_update(&client, &latest, &assets).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether to check for a new version after `command`. Not for the daemon,
+/// and not for commands whose output is read by other programs: the banner
+/// and prompt would end up in it.
+fn offers_update(command: &Commands) -> bool {
+    !matches!(
+        command,
+        Commands::Daemon { .. }
+            | Commands::Completions { .. }
+            | Commands::Man { .. }
+            | Commands::Status { .. }
+            | Commands::Events {
+                output: Output::Ndjson,
+                ..
+            }
+            | Commands::NowPlaying {
+                output: Output::Ndjson,
+                ..
+            }
+    )
+}
+
+/// Whether `command` runs the hooks from settings.json, so a broken file has
+/// to stop it rather than fall back to the defaults.
+fn runs_hooks(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Daemon { command: None, .. } | Commands::Events { hooks: true, .. }
+    )
+}
+
+/// Run one parsed command line. Aliases re-enter here with the expanded
+/// command lines, `depth` counting the nesting.
+async fn execute(cli: Cli, client: &Client, settings: &Settings, depth: usize) -> Result<()> {
     let token = cli.token.clone().or_else(load_token);
 
     match cli.command {
-        Commands::Health => health(&client, &cli.base_url).await?,
+        Commands::Health => health(client, &cli.base_url).await?,
         Commands::Play {
             query,
+            file,
+            concurrency,
+            shuffle,
+            source,
             guild_id,
             channel_id,
             user_id,
@@ -325,6 +861,7 @@ async fn main() -> Result<()> {
             let avatar = avatar_url.or_else(|| saved.as_ref().and_then(|a| a.avatar_url.clone()));
             let requested_by =
                 requested_by.or_else(|| saved.as_ref().and_then(|a| a.username.clone()));
+            let source = source.or(load_profile_settings(&cli.profile).default_source);
             let payload = PlayPayload {
                 action: "play",
                 guild_id,
@@ -333,30 +870,166 @@ async fn main() -> Result<()> {
                 user_id,
                 requested_by,
                 avatar_url: avatar,
+                position: None,
             };
-            post_audio(&client, &cli.base_url, token.as_deref(), &payload).await?;
+            let playlist = file.or_else(|| (query == ["-"]).then(|| PathBuf::from("-")));
+            if let Some(name_or_tag) = payload.query.strip_prefix("fav:") {
+                let selected = favorites::select(&cli.profile, name_or_tag, shuffle)?;
+                play_favorites(client, &cli.base_url, token.as_deref(), selected, &payload).await?;
+            } else if let Some(path) = upload::local_path(&payload.query) {
+                let uri = upload::upload(client, &cli.base_url, token.as_deref(), &path).await?;
+                let payload = PlayPayload {
+                    query: uri,
+                    ..payload
+                };
+                post_audio(client, &cli.base_url, token.as_deref(), &payload).await?;
+            } else if let Some(path) = playlist {
+                let entries = playlist::read_entries(&path)?;
+                let payloads = playlist::payloads(entries, &payload, source);
+                let total = payloads.len();
+                let failed = playlist::enqueue_all(
+                    client,
+                    &cli.base_url,
+                    token.as_deref(),
+                    payloads,
+                    concurrency,
+                )
+                .await;
+                if failed == total {
+                    bail!("no playlist entries could be queued");
+                }
+            } else {
+                let payload = PlayPayload {
+                    query: search::resolve_query(&payload.query, source),
+                    ..payload
+                };
+                post_audio(client, &cli.base_url, token.as_deref(), &payload).await?;
+            }
         }
-        Commands::Turip {
+        Commands::Search {
+            query,
+            source,
+            limit,
+            pick,
+            select,
             guild_id,
             channel_id,
             user_id,
             requested_by,
             avatar_url,
         } => {
-            let saved = load_auth();
-            let avatar = avatar_url.or_else(|| saved.as_ref().and_then(|a| a.avatar_url.clone()));
-            let requested_by =
-                requested_by.or_else(|| saved.as_ref().and_then(|a| a.username.clone()));
-            let payload = PlayPayload {
-                action: "play",
-                guild_id,
-                channel_id,
-                query: clean_query("https://open.spotify.com/track/2RQWB4Asy1rjZL4IUcJ7kn"),
-                user_id,
-                requested_by,
-                avatar_url: avatar,
+            let payload = SearchPayload {
+                action: "search",
+                guild_id: guild_id.clone(),
+                user_id: user_id.clone(),
+                query: search::search_query(
+                    &query.join(" "),
+                    source
+                        .or(load_profile_settings(&cli.profile).default_source)
+                        .unwrap_or(search::SearchSource::Youtube),
+                ),
+                limit,
             };
-            post_audio(&client, &cli.base_url, token.as_deref(), &payload).await?;
+            let json = fetch_audio(client, &cli.base_url, token.as_deref(), &payload).await?;
+            let tracks = search::parse_tracks(&json);
+            if tracks.is_empty() {
+                println!("{} No results for {}", "✘".red(), query.join(" ").bold());
+            } else {
+                println!("{} Results for {}", "🔎".cyan(), query.join(" ").bold());
+                search::print_results(&tracks);
+                if (pick || select.is_some())
+                    && let Some(idx) = search::pick(&tracks, select)?
+                {
+                    let saved = load_auth();
+                    let avatar =
+                        avatar_url.or_else(|| saved.as_ref().and_then(|a| a.avatar_url.clone()));
+                    let requested_by =
+                        requested_by.or_else(|| saved.as_ref().and_then(|a| a.username.clone()));
+                    let payload = PlayPayload {
+                        action: "play",
+                        guild_id,
+                        channel_id,
+                        query: tracks[idx].uri.clone(),
+                        user_id,
+                        requested_by,
+                        avatar_url: avatar,
+                        position: None,
+                    };
+                    post_audio(client, &cli.base_url, token.as_deref(), &payload).await?;
+                }
+            }
+        }
+        Commands::History {
+            command,
+            since,
+            grep,
+            limit,
+        } => {
+            let entries = history::query(since, grep.as_deref(), None);
+            match command {
+                None => print_history(&entries, limit),
+                Some(HistorySubcommand::Replay {
+                    n,
+                    guild_id,
+                    channel_id,
+                    user_id,
+                }) => {
+                    let entry = n
+                        .checked_sub(1)
+                        .and_then(|i| entries.get(i))
+                        .with_context(|| format!("no history entry #{}", n))?;
+                    let payload = history_play_payload(entry, guild_id, channel_id, user_id, None);
+                    post_audio(client, &cli.base_url, token.as_deref(), &payload).await?;
+                }
+            }
+        }
+        Commands::Fav { command } => {
+            fav_command(client, &cli.base_url, token.as_deref(), &cli.profile, command).await?;
+        }
+        Commands::Profile {
+            command: ProfileSubcommand::Source { source, clear },
+        } => {
+            let mut settings = load_profile_settings(&cli.profile);
+            if source.is_some() || clear {
+                settings.default_source = source;
+                save_profile_settings(&cli.profile, &settings)?;
+            }
+            match settings.default_source {
+                Some(source) => println!(
+                    "{} Default source for profile {}: {}",
+                    "🔎".cyan(),
+                    cli.profile.bold(),
+                    source.short_name().green()
+                ),
+                None => println!(
+                    "{} Profile {} uses the server's default source",
+                    "🔎".cyan(),
+                    cli.profile.bold()
+                ),
+            }
+        }
+        Commands::Stats {
+            since,
+            guild,
+            top,
+            output,
+        } => {
+            let entries = history::query(Some(since), None, guild.as_deref());
+            let report = stats::compute(&entries, top);
+            match output {
+                OutputFormat::Text => stats::print_table(&report),
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            }
+        }
+        Commands::Previous {
+            guild_id,
+            channel_id,
+            user_id,
+        } => {
+            let entry = history::last_finished()
+                .context("no finished track in history yet (see `jorik history`)")?;
+            let payload = history_play_payload(&entry, guild_id, channel_id, user_id, Some(0));
+            post_audio(client, &cli.base_url, token.as_deref(), &payload).await?;
         }
         Commands::Skip { guild_id, user_id } => {
             let payload = SimplePayload {
@@ -364,7 +1037,7 @@ async fn main() -> Result<()> {
                 guild_id,
                 user_id,
             };
-            post_audio(&client, &cli.base_url, token.as_deref(), &payload).await?;
+            post_undoable(client, &cli.base_url, token.as_deref(), &payload).await?;
         }
         Commands::Stop { guild_id, user_id } => {
             let payload = SimplePayload {
@@ -372,7 +1045,84 @@ async fn main() -> Result<()> {
                 guild_id,
                 user_id,
             };
-            post_audio(&client, &cli.base_url, token.as_deref(), &payload).await?;
+            let threshold = settings.confirm_threshold;
+            if confirm_wipe(client, &cli.base_url, token.as_deref(), &payload, cli.yes, threshold)
+                .await?
+            {
+                post_undoable(client, &cli.base_url, token.as_deref(), &payload).await?;
+            }
+        }
+        Commands::Events {
+            types,
+            guild,
+            user_id,
+            since_connect,
+            no_spectrogram,
+            output,
+            hooks,
+        } => {
+            let hooks = if hooks {
+                let runner = hooks::Runner::from_settings(settings);
+                if runner.is_none() {
+                    eprintln!("{} No hooks configured in settings.json", "ℹ️".blue());
+                }
+                runner
+            } else {
+                None
+            };
+            let opts = events::Options {
+                types,
+                guild_id: guild,
+                user_id,
+                since_connect,
+                no_spectrogram,
+                output,
+                hooks,
+            };
+            events::run(client, &cli.base_url, token.as_deref(), opts).await?;
+        }
+        Commands::Exec {
+            ref script,
+            continue_on_error,
+        } => {
+            exec_script(&cli, client, settings, script.as_deref(), continue_on_error, depth)
+                .await?;
+        }
+        Commands::Wait { args } => {
+            let condition = args.condition;
+            if !wait::run(client, &cli.base_url, token.as_deref(), args).await? {
+                // From the shell or a macro this would end the whole process
+                if depth > 0 {
+                    bail!("timed out waiting for {}", condition.name());
+                }
+                eprintln!(
+                    "{} Timed out waiting for {}",
+                    "⌛".yellow(),
+                    condition.name()
+                );
+                std::process::exit(wait::TIMEOUT_EXIT_CODE);
+            }
+        }
+        Commands::Undo {
+            channel_id,
+            user_id,
+        } => {
+            let summary =
+                undo::restore(client, &cli.base_url, token.as_deref(), channel_id, user_id).await?;
+            println!("{} {}", "↩️".green(), summary);
+        }
+        Commands::Seek {
+            position,
+            guild_id,
+            user_id,
+        } => {
+            let payload = SeekPayload {
+                action: "seek",
+                guild_id,
+                user_id,
+                position_ms: position.as_millis() as u64,
+            };
+            post_audio(client, &cli.base_url, token.as_deref(), &payload).await?;
         }
         Commands::Pause { guild_id, user_id } => {
             let payload = SimplePayload {
@@ -380,22 +1130,43 @@ async fn main() -> Result<()> {
                 guild_id,
                 user_id,
             };
-            post_audio(&client, &cli.base_url, token.as_deref(), &payload).await?;
+            post_audio(client, &cli.base_url, token.as_deref(), &payload).await?;
         }
         Commands::Queue {
+            command: Some(command),
+            guild_id,
+            user_id,
+            follow,
+            ..
+        } => {
+            if follow {
+                bail!("--follow only applies to the queue listing, not to subcommands");
+            }
+            queue_command(client, &cli.base_url, token.as_deref(), command, guild_id, user_id)
+                .await?;
+        }
+        Commands::Queue {
+            command: None,
             guild_id,
             user_id,
             limit,
             offset,
+            follow,
+            output,
         } => {
             let payload = QueuePayload {
                 action: "queue",
-                guild_id,
+                guild_id: guild_id.clone(),
                 user_id,
                 limit,
                 offset,
             };
-            post_audio(&client, &cli.base_url, token.as_deref(), &payload).await?;
+            if follow {
+                follow::run(client, &cli.base_url, token.as_deref(), guild_id, &payload, output)
+                    .await?;
+            } else {
+                post_audio(client, &cli.base_url, token.as_deref(), &payload).await?;
+            }
         }
         Commands::Clear { guild_id, user_id } => {
             let payload = SimplePayload {
@@ -403,15 +1174,33 @@ async fn main() -> Result<()> {
                 guild_id,
                 user_id,
             };
-            post_audio(&client, &cli.base_url, token.as_deref(), &payload).await?;
+            let threshold = settings.confirm_threshold;
+            if confirm_wipe(client, &cli.base_url, token.as_deref(), &payload, cli.yes, threshold)
+                .await?
+            {
+                post_undoable(client, &cli.base_url, token.as_deref(), &payload).await?;
+            }
         }
-        Commands::NowPlaying { guild_id, user_id } => {
+        Commands::NowPlaying {
+            guild_id,
+            user_id,
+            follow,
+            output,
+        } => {
             let payload = SimplePayload {
                 action: "nowplaying",
-                guild_id,
+                guild_id: guild_id.clone(),
                 user_id,
             };
-            post_audio(&client, &cli.base_url, token.as_deref(), &payload).await?;
+            if follow {
+                follow::run(client, &cli.base_url, token.as_deref(), guild_id, &payload, output)
+                    .await?;
+            } else {
+                post_audio(client, &cli.base_url, token.as_deref(), &payload).await?;
+            }
+        }
+        Commands::Status { args } => {
+            status::run(client, &cli.base_url, token.as_deref(), args).await?
         }
         Commands::Loop {
             mode,
@@ -424,7 +1213,7 @@ async fn main() -> Result<()> {
                 user_id,
                 loop_mode: mode,
             };
-            post_audio(&client, &cli.base_url, token.as_deref(), &payload).await?;
+            post_audio(client, &cli.base_url, token.as_deref(), &payload).await?;
         }
         Commands::TwentyFourSeven {
             state,
@@ -442,7 +1231,7 @@ async fn main() -> Result<()> {
                 user_id,
                 enabled,
             };
-            post_audio(&client, &cli.base_url, token.as_deref(), &payload).await?;
+            post_audio(client, &cli.base_url, token.as_deref(), &payload).await?;
         }
         Commands::Shuffle { guild_id, user_id } => {
             let payload = SimplePayload {
@@ -450,14 +1239,14 @@ async fn main() -> Result<()> {
                 guild_id,
                 user_id,
             };
-            post_audio(&client, &cli.base_url, token.as_deref(), &payload).await?;
+            post_undoable(client, &cli.base_url, token.as_deref(), &payload).await?;
         }
         Commands::Auth { command } => match command {
             AuthSubcommand::Login => {
                 login(&cli.base_url).await?;
             }
             AuthSubcommand::Signout => {
-                signout(&client, &cli.base_url, token.as_deref()).await?;
+                signout(client, &cli.base_url, token.as_deref()).await?;
             }
             AuthSubcommand::Info => {
                 auth_info()?;
@@ -469,125 +1258,338 @@ async fn main() -> Result<()> {
                 guild_id,
                 user_id,
             };
-            post_audio(&client, &cli.base_url, token.as_deref(), &payload).await?;
+            post_audio(client, &cli.base_url, token.as_deref(), &payload).await?;
         }
         Commands::Filter {
             style,
+            command,
             guild_id,
             user_id,
         } => {
-            let filters = match style.to_lowercase().as_str() {
-                "clear" => AudioFilters::default(),
-                "bassboost" => AudioFilters {
-                    equalizer: Some(vec![
-                        EqualizerBand { band: 0, gain: 0.2 },
-                        EqualizerBand {
-                            band: 1,
-                            gain: 0.15,
-                        },
-                        EqualizerBand { band: 2, gain: 0.1 },
-                        EqualizerBand {
-                            band: 3,
-                            gain: 0.05,
-                        },
-                        EqualizerBand { band: 4, gain: 0.0 },
-                        EqualizerBand {
-                            band: 5,
-                            gain: -0.05,
-                        },
-                    ]),
-                    ..Default::default()
-                },
-                "soft" => AudioFilters {
-                    low_pass: Some(LowPassOptions {
-                        smoothing: Some(20.0),
-                    }),
-                    ..Default::default()
-                },
-                "nightcore" => AudioFilters {
-                    timescale: Some(TimescaleOptions {
-                        speed: Some(1.1),
-                        pitch: Some(1.1),
-                        rate: Some(1.0),
-                    }),
-                    ..Default::default()
-                },
-                "vaporwave" => AudioFilters {
-                    timescale: Some(TimescaleOptions {
-                        speed: Some(0.85),
-                        pitch: Some(0.8),
-                        rate: Some(1.0),
-                    }),
-                    ..Default::default()
-                },
-                "8d" => AudioFilters {
-                    rotation: Some(RotationOptions {
-                        rotation_hz: Some(0.2),
-                    }),
-                    ..Default::default()
-                },
-                "tremolo" => AudioFilters {
-                    tremolo: Some(TremoloOptions {
-                        frequency: Some(2.0),
-                        depth: Some(0.5),
-                    }),
-                    ..Default::default()
-                },
-                "vibrato" => AudioFilters {
-                    vibrato: Some(VibratoOptions {
-                        frequency: Some(2.0),
-                        depth: Some(0.5),
-                    }),
-                    ..Default::default()
-                },
-                "karaoke" => AudioFilters {
-                    karaoke: Some(KaraokeOptions {
-                        level: Some(1.0),
-                        mono_level: Some(1.0),
-                        filter_band: Some(220.0),
-                        filter_width: Some(100.0),
-                    }),
-                    ..Default::default()
-                },
-                _ => {
-                    eprintln!("Unknown filter style: {}", style);
-                    return Ok(());
-                }
-            };
-
-            let payload = FilterPayload {
-                action: "filter",
+            filter_command(
+                client,
+                &cli.base_url,
+                token.as_deref(),
+                style,
+                command,
                 guild_id,
                 user_id,
-                filters,
-            };
-            post_audio(&client, &cli.base_url, token.as_deref(), &payload).await?;
+            )
+            .await?;
+        }
+        Commands::Run { name: None, .. } => list_aliases(settings),
+        Commands::Run {
+            name: Some(ref name),
+            ref args,
+        } => run_alias(&cli, client, settings, name, args, depth).await?,
+        Commands::External(ref args) => {
+            let (name, args) = args.split_first().context("missing alias name")?;
+            run_alias(&cli, client, settings, name, args, depth).await?;
         }
-        Commands::Tui { .. } => unreachable!(), // Handled early
+        Commands::Completions { shell } => {
+            let exe = std::env::current_exe().context("locating the jorik binary")?;
+            shell.completer().write_registration(
+                "COMPLETE",
+                "jorik",
+                "jorik",
+                &exe.to_string_lossy(),
+                &mut io::stdout(),
+            )?;
+        }
+        Commands::Man { ref output } => match output {
+            Some(dir) => {
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("creating {}", dir.display()))?;
+                clap_mangen::generate_to(cli_command(), dir)
+                    .with_context(|| format!("writing man pages to {}", dir.display()))?;
+                println!("{} Wrote man pages to {}", "✔".green(), dir.display());
+            }
+            None => clap_mangen::Man::new(cli_command()).render(&mut io::stdout())?,
+        },
+        Commands::Shell { .. } if depth > 0 => {
+            bail!("the shell cannot be started from a macro, script or another shell")
+        }
+        Commands::Shell {
+            ref guild_id,
+            ref user_id,
+        } => run_shell(&cli, client, settings, guild_id.clone(), user_id.clone()).await?,
+        Commands::Daemon { command: None, .. } if depth > 0 => {
+            bail!("the daemon cannot be started from a macro, script or the shell")
+        }
+        Commands::Daemon {
+            ref command,
+            ref guild_id,
+            ref user_id,
+        } => match command {
+            None => {
+                daemon::run(
+                    client.clone(),
+                    cli.base_url.clone(),
+                    token,
+                    hooks::Runner::from_settings(settings),
+                    guild_id.clone(),
+                    user_id.clone(),
+                )
+                .await?
+            }
+            Some(DaemonSubcommand::Status) => daemon::print_status().await?,
+            Some(DaemonSubcommand::Stop) => daemon::stop().await?,
+        },
+        #[cfg(target_os = "linux")]
+        Commands::Mpris { guild_id, user_id } => {
+            mpris::run(client, &cli.base_url, token.as_deref(), guild_id, user_id).await?
+        }
+        #[cfg(not(target_os = "linux"))]
+        Commands::Mpris { .. } => bail!("MPRIS is only available on Linux"),
+        Commands::Tui { .. } => bail!("the TUI cannot be started from a macro"),
     }
+    Ok(())
+}
 
-    if let Ok(Some((latest, assets))) = update_check.await {
-        println!(
-            "\n{} {} -> {}",
-            "A new version of jorik-cli is available:".yellow().bold(),
-            env!("CARGO_PKG_VERSION").red(),
-            latest.green().bold()
-        );
+async fn run_alias(
+    cli: &Cli,
+    client: &Client,
+    settings: &Settings,
+    name: &str,
+    args: &[String],
+    depth: usize,
+) -> Result<()> {
+    let aliases = aliases::all(settings);
+    let alias = aliases::lookup(&aliases, name, depth)?;
+    let steps = aliases::expand(name, alias, args)?;
+    let is_macro = steps.len() > 1 || matches!(alias, aliases::Alias::Macro(_));
+    for step in steps {
+        if is_macro {
+            println!("{} {}", "▶".cyan(), step.join(" ").dimmed());
+        }
+        let argv = ["jorik".to_string()].into_iter().chain(step);
+        let mut sub = Cli::try_parse_from(argv)
+            .with_context(|| format!("invalid step in alias {:?}", name))?;
+        sub.base_url = cli.base_url.clone();
+        sub.token = cli.token.clone();
+        sub.profile = cli.profile.clone();
+        sub.yes |= cli.yes;
+        Box::pin(execute(sub, client, settings, depth + 1)).await?;
+    }
+    Ok(())
+}
 
-        print!("Do you want to update and install the latest version? [y/N]: ");
-        io::stdout().flush()?;
+/// Run a `jorik exec` script on one client and, for `wait` lines, one
+/// WebSocket subscription.
+async fn exec_script(
+    cli: &Cli,
+    client: &Client,
+    settings: &Settings,
+    path: Option<&std::path::Path>,
+    continue_on_error: bool,
+    depth: usize,
+) -> Result<()> {
+    if depth >= aliases::MAX_DEPTH {
+        bail!("exec nests too deeply (script running itself?)");
+    }
+    let lines = script::parse(&script::read(path)?)?;
+    // Parse every line up front so a typo at the end fails before anything runs
+    let mut steps = Vec::new();
+    for line in lines {
+        let command = match &line.step {
+            script::Step::Sleep(_) => None,
+            script::Step::Command(words) => {
+                let argv = ["jorik".to_string()].into_iter().chain(words.iter().cloned());
+                let mut sub = Cli::try_parse_from(argv)
+                    .with_context(|| format!("line {}: {}", line.number, line.text))?;
+                sub.base_url = cli.base_url.clone();
+                sub.token = cli.token.clone();
+                sub.profile = cli.profile.clone();
+                sub.yes |= cli.yes;
+                Some(sub)
+            }
+        };
+        steps.push((line, command));
+    }
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+    let token = cli.token.clone().or_else(load_token);
+    let mut subscription = None;
+    let total = steps.len();
+    let mut ran = 0;
+    let mut failed = Vec::new();
+    for (line, command) in steps {
+        println!("{} {}", "▶".cyan(), line.text.dimmed());
+        ran += 1;
+        let result = match (line.step, command) {
+            (script::Step::Sleep(duration), _) => {
+                if api::dry_run().is_none() {
+                    tokio::time::sleep(duration).await;
+                }
+                Ok(())
+            }
+            (
+                _,
+                Some(Cli {
+                    command: Commands::Wait { args },
+                    ..
+                }),
+            ) => {
+                // A timeout is an ordinary failure here, not an exit
+                let condition = args.condition;
+                match wait::run_with(client, &cli.base_url, token.as_deref(), args, &mut subscription)
+                    .await
+                {
+                    Ok(false) => Err(anyhow::anyhow!("timed out waiting for {}", condition.name())),
+                    result => result.map(|_| ()),
+                }
+            }
+            (_, Some(sub)) => Box::pin(execute(sub, client, settings, depth + 1)).await,
+            (_, None) => Ok(()),
+        };
+        if let Err(e) = result {
+            eprintln!("{} line {}: {:#}", "✘".red(), line.number, e);
+            failed.push(line.number.to_string());
+            if !continue_on_error {
+                break;
+            }
+        }
+    }
 
-        if input.trim().eq_ignore_ascii_case("y") {
-            trigger_update(&client, &latest, &assets).await?;
+    if failed.is_empty() {
+        println!("{} {} commands succeeded", "✔".green(), ran);
+        return Ok(());
+    }
+    let skipped = if ran < total {
+        format!(", {} not run", total - ran)
+    } else {
+        String::new()
+    };
+    bail!(
+        "{} of {} commands failed (line {}){}",
+        failed.len(),
+        ran,
+        failed.join(", "),
+        skipped
+    )
+}
+
+/// `jorik shell`: read commands at a prompt and run them like `exec` runs
+/// script lines, until `exit` or Ctrl+D.
+async fn run_shell(
+    cli: &Cli,
+    client: &Client,
+    settings: &Settings,
+    guild_id: Option<String>,
+    user_id: Option<String>,
+) -> Result<()> {
+    let helper = shell::ShellHelper::new(Cli::command(), cli.profile.clone());
+    let config = rustyline::Config::builder()
+        .completion_type(rustyline::CompletionType::List)
+        .build();
+    let mut editor: rustyline::Editor<shell::ShellHelper, rustyline::history::DefaultHistory> =
+        rustyline::Editor::with_config(config)?;
+    editor.set_helper(Some(helper));
+    let history_path = shell_history_file_path();
+    if let Some(path) = &history_path {
+        let _ = editor.load_history(path);
+    }
+
+    let token = cli.token.clone().or_else(load_token);
+    if let Some(token) = token.as_deref()
+        && api::dry_run().is_none()
+    {
+        let guild = match guild_id {
+            Some(id) => Some(id),
+            None => ws::discover_guild(client, &cli.base_url, token, user_id).await.ok(),
+        };
+        match (guild, editor.create_external_printer()) {
+            (Some(guild), Ok(printer)) => {
+                shell::spawn_notices(printer, ws::Subscription::new(&cli.base_url, token, guild))
+            }
+            (None, _) => println!(
+                "{}",
+                "Not watching track changes: no guild found (pass --guild-id)".dimmed()
+            ),
+            (_, Err(_)) => {}
+        }
+    }
+    println!(
+        "{}",
+        "Type a command (`help` for the list), Tab to complete, `exit` or Ctrl+D to leave."
+            .dimmed()
+    );
+
+    loop {
+        let line = match tokio::task::block_in_place(|| editor.readline("jorik> ")) {
+            Ok(line) => line,
+            Err(rustyline::error::ReadlineError::Interrupted) => continue,
+            Err(rustyline::error::ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+        if matches!(line, "exit" | "quit") {
+            break;
+        }
+        let Some(mut words) = shlex::split(line) else {
+            println!("{} unbalanced quotes", "✘".red());
+            continue;
+        };
+        if words.first().is_some_and(|w| w == "jorik") {
+            words.remove(0);
+        }
+        let argv = ["jorik".to_string()].into_iter().chain(words);
+        let mut sub = match Cli::try_parse_from(argv) {
+            Ok(sub) => sub,
+            Err(e) => {
+                // Also covers `help` and `--help`
+                let _ = e.print();
+                continue;
+            }
+        };
+        sub.base_url = cli.base_url.clone();
+        sub.token = cli.token.clone();
+        sub.profile = cli.profile.clone();
+        sub.yes |= cli.yes;
+        // Ctrl+C stops the running command (say `events`), not the shell
+        let result = tokio::select! {
+            result = Box::pin(execute(sub, client, settings, 1)) => result,
+            _ = tokio::signal::ctrl_c() => {
+                println!();
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            println!("{} {:#}", "✘".red(), e);
         }
     }
 
+    if let Some(path) = &history_path {
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let _ = editor.save_history(path);
+    }
     Ok(())
 }
 
+fn list_aliases(settings: &Settings) {
+    let aliases = aliases::all(settings);
+    println!("{}", "Aliases".bold().underline());
+    let width = aliases.keys().map(|k| k.len()).max().unwrap_or(0);
+    for (name, alias) in &aliases {
+        let kind = match alias {
+            aliases::Alias::Query(_) => "play",
+            aliases::Alias::Macro(_) => "macro",
+        };
+        println!(
+            "  {:width$}  {}  {}",
+            name.bold(),
+            format!("{:5}", kind).dimmed(),
+            aliases::describe(alias),
+            width = width
+        );
+    }
+}
+
 async fn trigger_update(client: &Client, _latest: &str, assets: &[GiteaAsset]) -> Result<()> {
     if cfg!(target_os = "linux") {
         println!("Running update script...");
@@ -674,20 +1676,140 @@ async fn post_audio<T: serde::Serialize>(
     token: Option<&str>,
     payload: &T,
 ) -> Result<()> {
+    if !post_audio_accepted(client, base_url, token, payload).await? {
+        // print_response already showed why
+        bail!("the server rejected the request");
+    }
+    Ok(())
+}
+
+/// [`post_audio`], but a rejection is reported as `false` rather than an error
+/// (always `true` under `--dry-run`).
+async fn post_audio_accepted<T: serde::Serialize>(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    payload: &T,
+) -> Result<bool> {
     let url = build_url(base_url, "/webhook/audio");
-    let mut req = client.post(&url).json(payload);
-    if let Some(bearer) = token {
-        req = req.bearer_auth(bearer);
+    if api::dry_run().is_some() {
+        api::print_dry_run(&url, token, payload);
+        return Ok(true);
+    }
+    let (status, text) = match daemon::request(base_url, token, payload).await? {
+        Some(reply) => (reply.status(), reply.body),
+        None => {
+            let resp = api::send_audio(client, base_url, token, payload).await?;
+            let status = resp.status();
+            (status, resp.text().await.context("reading response body")?)
+        }
+    };
+    print_response(status, &text)?;
+    Ok(api::parse_response(status, &text, &url).is_ok())
+}
+
+/// Send an undoable action, snapshotting the queue first. A failed snapshot
+/// only costs the undo, not the action.
+async fn post_undoable(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    payload: &SimplePayload,
+) -> Result<()> {
+    let snapshot = undo::take(
+        client,
+        base_url,
+        token,
+        payload.action,
+        payload.guild_id.clone(),
+        payload.user_id.clone(),
+    )
+    .await;
+    if let Err(e) = &snapshot {
+        eprintln!("{} Could not snapshot the queue, undo is unavailable: {}", "⚠".yellow(), e);
     }
-    let resp = req.send().await.with_context(|| format!("POST {url}"))?;
-    print_response(resp).await
+    if !post_audio_accepted(client, base_url, token, payload).await? {
+        // Not saving the snapshot: restoring it would enqueue the still-intact
+        // queue a second time
+        bail!("the server rejected the request");
+    }
+    if let Ok(Some(snapshot)) = snapshot {
+        match snapshot.save() {
+            Ok(()) => println!("{}", "Run `jorik undo` to restore the previous queue".dimmed()),
+            Err(e) => eprintln!("{} Could not save the undo snapshot: {:#}", "⚠".yellow(), e),
+        }
+    }
+    Ok(())
+}
+
+/// Parse `1:23`, `1:02:03`, plain seconds (`83`) or humantime (`1m30s`).
+fn parse_position(s: &str) -> Result<Duration, String> {
+    if let Ok(secs) = s.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+    if s.contains(':') {
+        let mut secs = 0u64;
+        for part in s.split(':') {
+            let n: u64 = part.parse().map_err(|_| format!("invalid position {:?}", s))?;
+            secs = secs * 60 + n;
+        }
+        return Ok(Duration::from_secs(secs));
+    }
+    humantime::parse_duration(s).map_err(|e| e.to_string())
 }
 
-async fn print_response(resp: reqwest::Response) -> Result<()> {
-    let status = resp.status();
-    let text = resp.text().await.context("reading response body")?;
+/// Ask before `stop`/`clear` wipes a queue longer than `threshold`. Without a
+/// terminal to ask on, refuse unless `--yes` was given.
+async fn confirm_wipe(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    payload: &SimplePayload,
+    yes: bool,
+    threshold: usize,
+) -> Result<bool> {
+    if yes || api::dry_run().is_some() {
+        return Ok(true);
+    }
+    let count = queue::count(
+        client,
+        base_url,
+        token,
+        payload.guild_id.clone(),
+        payload.user_id.clone(),
+    )
+    .await?;
+    if count <= threshold {
+        return Ok(true);
+    }
+    let what = match payload.action {
+        "stop" => "stop playback and drop",
+        _ => "clear",
+    };
+    if !io::stdin().is_terminal() {
+        bail!(
+            "refusing to {} {} queued tracks without confirmation (pass --yes)",
+            what,
+            count
+        );
+    }
+    print!("{} Really {} {} queued tracks? [y/N]: ", "⚠".yellow(), what, count);
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if input.trim().eq_ignore_ascii_case("y") {
+        Ok(true)
+    } else {
+        println!("{} Cancelled", "✘".red());
+        Ok(false)
+    }
+}
 
-    if let Ok(json) = serde_json::from_str::<Value>(&text) {
+fn print_response(status: reqwest::StatusCode, text: &str) -> Result<()> {
+    if let Ok(json) = serde_json::from_str::<Value>(text) {
+        if status.is_success() {
+            history::record_response(&json);
+        }
         if let Some(summary) = summarize(&json) {
             println!("{}", summary);
         } else if !status.is_success() {
@@ -710,7 +1832,7 @@ async fn print_response(resp: reqwest::Response) -> Result<()> {
     Ok(())
 }
 
-fn summarize(json: &Value) -> Option<String> {
+pub fn summarize(json: &Value) -> Option<String> {
     let obj = json.as_object()?;
 
     // Handle Errors
@@ -967,6 +2089,455 @@ fn summarize(json: &Value) -> Option<String> {
     }
 }
 
+async fn filter_command(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    style: Option<String>,
+    command: Option<FilterSubcommand>,
+    guild_id: Option<String>,
+    user_id: Option<String>,
+) -> Result<()> {
+    let (filters, guild_id, user_id) = match (command, style) {
+        (Some(FilterSubcommand::Preset { command }), _) => return preset_command(command),
+        (
+            Some(FilterSubcommand::Show {
+                guild_id: sub_guild,
+                user_id: sub_user,
+            }),
+            _,
+        ) => {
+            let active = fetch_active_filters(
+                client,
+                base_url,
+                token,
+                sub_guild.or(guild_id),
+                sub_user.or(user_id),
+            )
+            .await?;
+            return show_active_filters(&active);
+        }
+        (
+            Some(FilterSubcommand::Add {
+                preset,
+                guild_id: sub_guild,
+                user_id: sub_user,
+            }),
+            _,
+        ) => {
+            let preset = filters::find_preset(&preset).with_context(|| {
+                format!(
+                    "unknown filter preset: {} (run `jorik filter preset list` to see available presets)",
+                    preset
+                )
+            })?;
+            let guild_id = sub_guild.or(guild_id);
+            let user_id = sub_user.or(user_id);
+            let mut active =
+                fetch_active_filters(client, base_url, token, guild_id.clone(), user_id.clone())
+                    .await?;
+            filters::merge(&mut active, &preset.filters);
+            filters::validate(&active)?;
+            (active, guild_id, user_id)
+        }
+        (
+            Some(FilterSubcommand::Remove {
+                kind,
+                guild_id: sub_guild,
+                user_id: sub_user,
+            }),
+            _,
+        ) => {
+            let guild_id = sub_guild.or(guild_id);
+            let user_id = sub_user.or(user_id);
+            let mut active =
+                fetch_active_filters(client, base_url, token, guild_id.clone(), user_id.clone())
+                    .await?;
+            if !kind.is_set(&active) {
+                println!(
+                    "{} {} filter is not active",
+                    "ℹ".blue(),
+                    kind.label().bold()
+                );
+                return Ok(());
+            }
+            kind.clear(&mut active);
+            (active, guild_id, user_id)
+        }
+        (
+            Some(FilterSubcommand::Custom {
+                args,
+                guild_id: sub_guild,
+                user_id: sub_user,
+            }),
+            _,
+        ) => (args.build()?, sub_guild.or(guild_id), sub_user.or(user_id)),
+        (None, Some(style)) => {
+            let preset = filters::find_preset(&style).with_context(|| {
+                format!(
+                    "unknown filter preset: {} (run `jorik filter preset list` to see available presets)",
+                    style
+                )
+            })?;
+            (preset.filters, guild_id, user_id)
+        }
+        (None, None) => bail!("specify a filter preset (see `jorik filter preset list`) or `custom`"),
+    };
+
+    let payload = FilterPayload {
+        action: "filter",
+        guild_id,
+        user_id,
+        filters,
+    };
+    post_audio(client, base_url, token, &payload).await
+}
+
+async fn queue_command(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    command: QueueSubcommand,
+    guild_id: Option<String>,
+    user_id: Option<String>,
+) -> Result<()> {
+    match command {
+        QueueSubcommand::Export {
+            format,
+            output,
+            guild_id: sub_guild,
+            user_id: sub_user,
+        } => {
+            let format = format
+                .or_else(|| output.as_deref().and_then(queue::ExportFormat::from_path))
+                .unwrap_or(queue::ExportFormat::Json);
+            let entries = queue::fetch_all(
+                client,
+                base_url,
+                token,
+                sub_guild.or(guild_id),
+                sub_user.or(user_id),
+            )
+            .await?;
+            let rendered = queue::render(&entries, format)?;
+            match output {
+                Some(path) => {
+                    fs::write(&path, rendered)
+                        .with_context(|| format!("writing {}", path.display()))?;
+                    eprintln!(
+                        "{} Exported {} tracks to {}",
+                        "✔".green(),
+                        entries.len().to_string().bold(),
+                        path.display()
+                    );
+                }
+                None => print!("{}", rendered),
+            }
+        }
+        QueueSubcommand::Import {
+            file,
+            guild_id: sub_guild,
+            channel_id,
+            user_id: sub_user,
+        } => {
+            let entries = queue::read(&file)?;
+            let saved = load_auth();
+            let payloads: Vec<PlayPayload> = entries
+                .into_iter()
+                .map(|entry| PlayPayload {
+                    action: "play",
+                    guild_id: sub_guild.clone().or_else(|| guild_id.clone()),
+                    channel_id: channel_id.clone(),
+                    query: clean_query(&entry.uri),
+                    user_id: sub_user.clone().or_else(|| user_id.clone()),
+                    requested_by: entry
+                        .requested_by
+                        .or_else(|| saved.as_ref().and_then(|a| a.username.clone())),
+                    avatar_url: saved.as_ref().and_then(|a| a.avatar_url.clone()),
+                    position: None,
+                })
+                .collect();
+            let total = payloads.len();
+            // One at a time so the tracks keep their original order
+            let failed = playlist::enqueue_all(client, base_url, token, payloads, 1).await;
+            if failed == total {
+                bail!("no tracks could be queued");
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn fav_command(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    profile: &str,
+    command: FavSubcommand,
+) -> Result<()> {
+    match command {
+        FavSubcommand::Add {
+            target,
+            tags,
+            name,
+            guild_id,
+            user_id,
+        } => {
+            let mut favorite = if target == ["current"] {
+                let payload = SimplePayload {
+                    action: "nowplaying",
+                    guild_id,
+                    user_id,
+                };
+                let json = fetch_audio(client, base_url, token, &payload).await?;
+                json.get("now_playing")
+                    .and_then(favorites::Favorite::from_track)
+                    .context("nothing is playing right now")?
+            } else {
+                favorites::Favorite::from_query(&clean_query(&target.join(" ")))
+            };
+            if let Some(name) = name {
+                favorite.name = name;
+            }
+            favorite.tags = tags;
+            let stored = favorites::add(profile, favorite)?;
+            println!(
+                "{} Saved {} as {}{}",
+                "⭐".yellow(),
+                stored.display_title().bold(),
+                stored.name.cyan(),
+                if stored.tags.is_empty() {
+                    String::new()
+                } else {
+                    format!(" [{}]", stored.tags.join(", "))
+                }
+            );
+        }
+        FavSubcommand::List { tag } => {
+            let favs: Vec<_> = favorites::load(profile)
+                .into_iter()
+                .filter(|f| tag.as_ref().is_none_or(|t| f.tags.contains(t)))
+                .collect();
+            if favs.is_empty() {
+                println!("{} No favorites yet (add one with `jorik fav add`)", "ℹ".blue());
+                return Ok(());
+            }
+            println!("{}", "Favorites".bold().underline());
+            let width = favs.iter().map(|f| f.name.len()).max().unwrap_or(0);
+            for fav in &favs {
+                println!(
+                    "  {:width$}  {}{}",
+                    fav.name.bold(),
+                    fav.display_title(),
+                    if fav.tags.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" [{}]", fav.tags.join(", ")).dimmed().to_string()
+                    },
+                    width = width
+                );
+            }
+        }
+        FavSubcommand::Play {
+            name_or_tag,
+            shuffle,
+            guild_id,
+            channel_id,
+            user_id,
+        } => {
+            let selected = favorites::select(profile, &name_or_tag, shuffle)?;
+            let saved = load_auth();
+            let template = PlayPayload {
+                action: "play",
+                guild_id,
+                channel_id,
+                query: String::new(),
+                user_id,
+                requested_by: saved.as_ref().and_then(|a| a.username.clone()),
+                avatar_url: saved.as_ref().and_then(|a| a.avatar_url.clone()),
+                position: None,
+            };
+            play_favorites(client, base_url, token, selected, &template).await?;
+        }
+        FavSubcommand::Rm { name } => {
+            let removed = favorites::remove(profile, &name)?;
+            println!("{} Removed favorite {}", "🗑️".red(), removed.name.bold());
+        }
+    }
+    Ok(())
+}
+
+/// Enqueue favorites in order, with a progress bar when there is more than one.
+async fn play_favorites(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    selected: Vec<favorites::Favorite>,
+    template: &PlayPayload,
+) -> Result<()> {
+    let mut payloads =
+        playlist::payloads(selected.into_iter().map(|f| f.query).collect(), template, None);
+    if payloads.len() == 1 {
+        let payload = payloads.remove(0);
+        post_audio(client, base_url, token, &payload).await?;
+    } else {
+        let total = payloads.len();
+        let failed = playlist::enqueue_all(client, base_url, token, payloads, 1).await;
+        if failed == total {
+            bail!("no favorites could be queued");
+        }
+    }
+    Ok(())
+}
+
+fn print_history(entries: &[history::HistoryEntry], limit: usize) {
+    if entries.is_empty() {
+        println!("{} No history recorded yet", "ℹ".blue());
+        return;
+    }
+    println!("{}", "Playback History".bold().underline());
+    for (i, entry) in entries.iter().take(limit).enumerate() {
+        let when = chrono::DateTime::from_timestamp(entry.at, 0)
+            .map(|t| {
+                t.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        println!(
+            "{:>3}. {} {:8} {}",
+            i + 1,
+            when.dimmed(),
+            entry.event.label(),
+            entry.display_title().bold()
+        );
+    }
+    if entries.len() > limit {
+        println!("... and {} more", entries.len() - limit);
+    }
+}
+
+fn history_play_payload(
+    entry: &history::HistoryEntry,
+    guild_id: Option<String>,
+    channel_id: Option<String>,
+    user_id: Option<String>,
+    position: Option<usize>,
+) -> PlayPayload {
+    let saved = load_auth();
+    PlayPayload {
+        action: "play",
+        guild_id: guild_id.or_else(|| entry.guild_id.clone()),
+        channel_id,
+        query: entry.uri.clone(),
+        user_id,
+        requested_by: saved.as_ref().and_then(|a| a.username.clone()),
+        avatar_url: saved.as_ref().and_then(|a| a.avatar_url.clone()),
+        position,
+    }
+}
+
+pub async fn fetch_active_filters(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    guild_id: Option<String>,
+    user_id: Option<String>,
+) -> Result<AudioFilters> {
+    let payload = SimplePayload {
+        action: "filters",
+        guild_id,
+        user_id,
+    };
+    let json = fetch_audio(client, base_url, token, &payload).await?;
+    if api::dry_run().is_some() {
+        // Nothing came back; the dry run shows the request instead
+        return Ok(AudioFilters::default());
+    }
+    // Guessing "none" here would make `filter add` wipe what is active
+    filters::from_response(&json)
+        .context("the server's response did not include the active filters")
+}
+
+fn show_active_filters(active: &AudioFilters) -> Result<()> {
+    let kinds = filters::active_kinds(active);
+    if kinds.is_empty() {
+        println!("{} No filters active", "🎚️".cyan());
+        return Ok(());
+    }
+    let badges: Vec<String> = kinds
+        .iter()
+        .map(|k| format!("[{}]", k.label()).bold().to_string())
+        .collect();
+    println!("{} Active filters: {}", "🎚️".cyan(), badges.join(" "));
+    let text = serde_json::to_string_pretty(active)?;
+    println!("{}", text.to_colored_json_auto()?);
+    Ok(())
+}
+
+fn preset_command(command: PresetSubcommand) -> Result<()> {
+    match command {
+        PresetSubcommand::List => {
+            let (presets, errors) = filters::load_presets();
+            println!("{}", "Filter presets".bold().underline());
+            let width = presets.iter().map(|p| p.name.len()).max().unwrap_or(0);
+            for preset in &presets {
+                let source = match &preset.path {
+                    Some(path) => path.display().to_string(),
+                    None => "built-in".to_string(),
+                };
+                println!(
+                    "  {:width$}  {}{}",
+                    preset.name.bold(),
+                    preset
+                        .description
+                        .as_deref()
+                        .map(|d| format!("{} ", d))
+                        .unwrap_or_default(),
+                    format!("({})", source).dimmed(),
+                    width = width
+                );
+            }
+            for err in errors {
+                eprintln!("{} {:#}", "⚠".yellow(), err);
+            }
+        }
+        PresetSubcommand::Show { name } => {
+            let preset = filters::find_preset(&name)
+                .with_context(|| format!("unknown filter preset: {}", name))?;
+            println!("{} {}", "🎚️".cyan(), preset.name.bold());
+            if let Some(desc) = &preset.description {
+                println!("{}", desc);
+            }
+            match &preset.path {
+                Some(path) => println!("Source: {}", path.display().to_string().dimmed()),
+                None => println!("Source: {}", "built-in".dimmed()),
+            }
+            let json = serde_json::to_string_pretty(&preset.filters).context("serializing filters")?;
+            println!(
+                "{}",
+                json.to_colored_json_auto().context("formatting filters")?
+            );
+        }
+        PresetSubcommand::Save {
+            name,
+            description,
+            format,
+            args,
+        } => {
+            let filters = args.build()?;
+            let path = filters::save_preset(&name, description, filters, format)?;
+            println!("{} Saved preset {} to {}", "✔".green(), name.bold(), path.display());
+        }
+        PresetSubcommand::Delete { name } => {
+            for path in filters::delete_preset(&name)? {
+                println!("{} Removed {}", "🗑️".red(), path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
 fn escape_html(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
@@ -0,0 +1,591 @@
+//! `jorik mpris`: an MPRIS player on the session bus, so media keys, desktop
+//! media widgets and `playerctl` can see and control the bot.
+//!
+//! Calls map onto the usual actions (PlayPause toggles `pause`, Next is
+//! `skip`, Volume is the volume filter, ...); Metadata and Position come from
+//! [`status::Watcher`]. MPRIS has no notion of a one-off shuffle, so setting
+//! Shuffle shuffles the queue once and the property reads false again.
+
+use crate::api::{
+    self, FilterPayload, LoopPayload, PlayPayload, SeekPayload, SimplePayload, fetch_audio,
+};
+use crate::status::{Now, Watcher};
+use anyhow::{Context, Result, bail};
+use colored::Colorize;
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::{Duration, Instant};
+use zbus::object_server::{InterfaceRef, SignalEmitter};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::{fdo, interface};
+
+pub const BUS_NAME: &str = "org.mpris.MediaPlayer2.jorik";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// Position jumps bigger than this (in µs) are announced with `Seeked`.
+const SEEK_THRESHOLD_US: i64 = 2_000_000;
+
+/// `org.mpris.MediaPlayer2`: identity only, there is no window to raise.
+struct Root;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> &str {
+        "Jorik"
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec!["http".to_string(), "https".to_string()]
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Position in µs `since_update` after `now` was right, up to the track's end.
+fn position_us(now: &Now, since_update: Duration) -> i64 {
+    let mut ms = now.elapsed_ms;
+    if !now.paused && now.title.is_some() {
+        ms += since_update.as_millis() as u64;
+    }
+    if now.duration_ms > 0 {
+        ms = ms.min(now.duration_ms);
+    }
+    ms as i64 * 1000
+}
+
+/// The MPRIS track id for `uri`, stable for as long as the track plays.
+fn track_path(uri: Option<&str>) -> String {
+    match uri {
+        Some(uri) => {
+            let mut hasher = DefaultHasher::new();
+            uri.hash(&mut hasher);
+            format!("/org/jorik/track/t{:016x}", hasher.finish())
+        }
+        None => NO_TRACK.to_string(),
+    }
+}
+
+/// Where a relative `Seek` by `offset_us` from `position_us` goes; `None`
+/// past the end, which MPRIS treats as Next.
+fn seek_target(now: &Now, position_us: i64, offset_us: i64) -> Option<i64> {
+    let target = position_us + offset_us;
+    if now.duration_ms > 0 && target > now.duration_ms as i64 * 1000 {
+        return None;
+    }
+    Some(target)
+}
+
+/// Whether `SetPosition` applies: it names the current track and a position
+/// within it. Anything else is ignored, as MPRIS asks.
+fn accepts_position(now: &Now, track_id: &str, position_us: i64) -> bool {
+    track_id == track_path(now.uri.as_deref())
+        && position_us >= 0
+        && (now.duration_ms == 0 || position_us <= now.duration_ms as i64 * 1000)
+}
+
+/// MPRIS `LoopStatus` for the bot's loop mode.
+fn loop_status(loop_mode: Option<&str>) -> &'static str {
+    match loop_mode {
+        Some("track") => "Track",
+        Some(_) => "Playlist",
+        None => "None",
+    }
+}
+
+/// The bot's loop mode for an MPRIS `LoopStatus`.
+fn loop_mode(status: &str) -> Option<&'static str> {
+    match status {
+        "Track" => Some("track"),
+        "Playlist" => Some("queue"),
+        "None" => Some("off"),
+        _ => None,
+    }
+}
+
+/// `org.mpris.MediaPlayer2.Player`, backed by the bot's actions.
+struct Player {
+    client: Client,
+    base_url: String,
+    token: String,
+    guild_id: String,
+    user_id: Option<String>,
+    now: Now,
+    /// When `now.elapsed_ms` was last right
+    at: Instant,
+    /// Volume filter multiplier
+    volume: f64,
+}
+
+impl Player {
+    async fn send<T: Serialize>(&self, payload: &T) -> fdo::Result<()> {
+        fetch_audio(&self.client, &self.base_url, Some(&self.token), payload)
+            .await
+            .map(|_| ())
+            .map_err(|e| fdo::Error::Failed(format!("{:#}", e)))
+    }
+
+    async fn simple(&self, action: &'static str) -> fdo::Result<()> {
+        self.send(&SimplePayload {
+            action,
+            guild_id: Some(self.guild_id.clone()),
+            user_id: self.user_id.clone(),
+        })
+        .await
+    }
+
+    /// Current position in µs, counting time since the last update.
+    fn position_us(&self) -> i64 {
+        position_us(&self.now, self.at.elapsed())
+    }
+
+    fn track_id(&self) -> OwnedObjectPath {
+        ObjectPath::try_from(track_path(self.now.uri.as_deref()))
+            .unwrap_or_else(|_| ObjectPath::from_static_str_unchecked(NO_TRACK))
+            .into()
+    }
+
+    async fn seek_to(&mut self, position_us: i64) -> fdo::Result<()> {
+        let position_ms = (position_us.max(0) / 1000) as u64;
+        self.send(&SeekPayload {
+            action: "seek",
+            guild_id: Some(self.guild_id.clone()),
+            user_id: self.user_id.clone(),
+            position_ms,
+        })
+        .await?;
+        self.now.elapsed_ms = position_ms;
+        self.at = Instant::now();
+        Ok(())
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    async fn play_pause(&self) -> fdo::Result<()> {
+        self.simple("pause").await
+    }
+
+    async fn play(&self) -> fdo::Result<()> {
+        if self.now.paused {
+            self.simple("pause").await?;
+        }
+        Ok(())
+    }
+
+    async fn pause(&self) -> fdo::Result<()> {
+        if !self.now.paused && self.now.title.is_some() {
+            self.simple("pause").await?;
+        }
+        Ok(())
+    }
+
+    async fn next(&self) -> fdo::Result<()> {
+        self.simple("skip").await
+    }
+
+    async fn previous(&self) -> fdo::Result<()> {
+        Err(fdo::Error::NotSupported("the bot has no previous track".to_string()))
+    }
+
+    async fn stop(&self) -> fdo::Result<()> {
+        self.simple("stop").await
+    }
+
+    /// Relative seek in µs; past the end skips, as MPRIS asks.
+    async fn seek(
+        &mut self,
+        offset: i64,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        let Some(target) = seek_target(&self.now, self.position_us(), offset) else {
+            return self.simple("skip").await;
+        };
+        self.seek_to(target).await?;
+        Self::seeked(&emitter, self.position_us()).await?;
+        Ok(())
+    }
+
+    async fn set_position(
+        &mut self,
+        track_id: ObjectPath<'_>,
+        position: i64,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        // E.g. meant for a track that has already ended
+        if !accepts_position(&self.now, track_id.as_str(), position) {
+            return Ok(());
+        }
+        self.seek_to(position).await?;
+        Self::seeked(&emitter, self.position_us()).await?;
+        Ok(())
+    }
+
+    async fn open_uri(&self, uri: &str) -> fdo::Result<()> {
+        self.send(&PlayPayload {
+            action: "play",
+            guild_id: Some(self.guild_id.clone()),
+            channel_id: None,
+            query: api::clean_query(uri),
+            user_id: self.user_id.clone(),
+            requested_by: None,
+            avatar_url: None,
+            position: None,
+        })
+        .await
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> &str {
+        match (&self.now.title, self.now.paused) {
+            (None, _) => "Stopped",
+            (Some(_), true) => "Paused",
+            (Some(_), false) => "Playing",
+        }
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> &str {
+        loop_status(self.now.loop_mode.as_deref())
+    }
+
+    #[zbus(property)]
+    async fn set_loop_status(&mut self, value: String) -> zbus::Result<()> {
+        let Some(mode) = loop_mode(&value) else {
+            return Err(fdo::Error::InvalidArgs(format!("unknown loop status {}", value)).into());
+        };
+        self.send(&LoopPayload {
+            action: "loop",
+            guild_id: Some(self.guild_id.clone()),
+            user_id: self.user_id.clone(),
+            loop_mode: mode.to_string(),
+        })
+        .await?;
+        self.now.loop_mode = (mode != "off").then(|| mode.to_string());
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    async fn set_shuffle(&mut self, value: bool) -> zbus::Result<()> {
+        if value {
+            self.simple("shuffle").await?;
+        }
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn set_rate(&mut self, _value: f64) {}
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.volume
+    }
+
+    #[zbus(property)]
+    async fn set_volume(&mut self, value: f64) -> zbus::Result<()> {
+        let value = value.clamp(0.0, 5.0);
+        let mut filters = crate::fetch_active_filters(
+            &self.client,
+            &self.base_url,
+            Some(&self.token),
+            Some(self.guild_id.clone()),
+            self.user_id.clone(),
+        )
+        .await
+        .map_err(|e| fdo::Error::Failed(format!("{:#}", e)))?;
+        filters.volume = Some(value as f32);
+        self.send(&FilterPayload {
+            action: "filter",
+            guild_id: Some(self.guild_id.clone()),
+            user_id: self.user_id.clone(),
+            filters,
+        })
+        .await?;
+        self.volume = value;
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let mut metadata = HashMap::new();
+        let mut insert = |key: &str, value: Value<'_>| {
+            if let Ok(value) = value.try_to_owned() {
+                metadata.insert(key.to_string(), value);
+            }
+        };
+        insert("mpris:trackid", Value::from(self.track_id()));
+        if self.now.duration_ms > 0 {
+            insert("mpris:length", Value::from(self.now.duration_ms as i64 * 1000));
+        }
+        if let Some(title) = &self.now.title {
+            insert("xesam:title", Value::from(title.as_str()));
+        }
+        if let Some(author) = &self.now.author {
+            insert("xesam:artist", Value::from(vec![author.as_str()]));
+        }
+        if let Some(uri) = &self.now.uri {
+            insert("xesam:url", Value::from(uri.as_str()));
+        }
+        if let Some(art) = &self.now.artwork_url {
+            insert("mpris:artUrl", Value::from(art.as_str()));
+        }
+        metadata
+    }
+
+    /// Clients poll this and extrapolate; changes only go out as `Seeked`.
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        self.position_us()
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// Register the player and keep it in sync until Ctrl+C.
+pub async fn run(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    guild_id: Option<String>,
+    user_id: Option<String>,
+) -> Result<()> {
+    if api::dry_run().is_some() {
+        bail!("the MPRIS player cannot be combined with --dry-run");
+    }
+    let mut watcher =
+        Watcher::start(client, base_url, token, guild_id, user_id.clone()).await?;
+    let token = token.context("the MPRIS player needs a login")?;
+    let volume = crate::fetch_active_filters(
+        client,
+        base_url,
+        Some(token),
+        Some(watcher.guild_id.clone()),
+        user_id.clone(),
+    )
+    .await
+    .ok()
+    .and_then(|f| f.volume)
+    .unwrap_or(1.0);
+
+    let player = Player {
+        client: client.clone(),
+        base_url: base_url.to_string(),
+        token: token.to_string(),
+        guild_id: watcher.guild_id.clone(),
+        user_id,
+        now: watcher.now.clone(),
+        at: Instant::now(),
+        volume: volume as f64,
+    };
+    let connection = zbus::connection::Builder::session()
+        .context("connecting to the D-Bus session bus")?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, Root)?
+        .serve_at(OBJECT_PATH, player)?
+        .build()
+        .await
+        .with_context(|| format!("registering {} (is another `jorik mpris` running?)", BUS_NAME))?;
+    let player = connection
+        .object_server()
+        .interface::<_, Player>(OBJECT_PATH)
+        .await?;
+    println!(
+        "{} Registered {} for guild {} (Ctrl+C to stop)",
+        "🎛️".cyan(),
+        BUS_NAME.bold(),
+        watcher.guild_id
+    );
+
+    loop {
+        tokio::select! {
+            update = watcher.next() => {
+                if update.is_none() {
+                    break;
+                }
+                if let Err(e) = sync(&player, &watcher).await {
+                    eprintln!("{} {:#}", "✘".red(), e);
+                }
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    Ok(())
+}
+
+/// Copy the watcher's state into the player and announce what changed.
+async fn sync(player: &InterfaceRef<Player>, watcher: &Watcher) -> Result<()> {
+    let emitter = player.signal_emitter();
+    let mut player = player.get_mut().await;
+    let before = (
+        player.playback_status().to_string(),
+        player.loop_status().to_string(),
+        player.metadata(),
+    );
+    let expected = player.position_us();
+
+    player.now = watcher.now.clone();
+    player.at = Instant::now();
+
+    if player.playback_status() != before.0 {
+        player.playback_status_changed(emitter).await?;
+    }
+    if player.loop_status() != before.1 {
+        player.loop_status_changed(emitter).await?;
+    }
+    let track_changed = player.metadata() != before.2;
+    if track_changed {
+        player.metadata_changed(emitter).await?;
+    }
+    let position = player.position_us();
+    if !track_changed && (position - expected).abs() > SEEK_THRESHOLD_US {
+        Player::seeked(emitter, position).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn now(paused: bool, loop_mode: Option<&str>) -> Now {
+        Now::from_response(&json!({
+            "now_playing": {
+                "track": {"title": "Song", "uri": "https://youtu.be/a"},
+                "elapsedMs": 30000,
+                "durationMs": 120000,
+                "paused": paused,
+            },
+            "loop": loop_mode,
+        }))
+    }
+
+    #[test]
+    fn extrapolates_the_position() {
+        let stopped = Now::from_response(&json!({"now_playing": null}));
+        let cases = [
+            (now(false, None), 5, 35_000_000),
+            // paused (or stopped) positions stand still
+            (now(true, None), 5, 30_000_000),
+            (stopped, 5, 0),
+            // clamped to the track's length
+            (now(false, None), 600, 120_000_000),
+        ];
+        for (now, secs, expected) in cases {
+            assert_eq!(position_us(&now, Duration::from_secs(secs)), expected);
+        }
+    }
+
+    #[test]
+    fn maps_loop_modes() {
+        for (mode, status) in [(Some("track"), "Track"), (Some("queue"), "Playlist"), (None, "None")] {
+            assert_eq!(loop_status(mode), status);
+            let sent = loop_mode(status).unwrap();
+            assert_eq!((sent != "off").then_some(sent), mode);
+        }
+        assert_eq!(loop_mode("Shuffle"), None);
+    }
+
+    #[test]
+    fn guards_set_position() {
+        let now = now(false, None);
+        let current = track_path(now.uri.as_deref());
+        let cases = [
+            (current.as_str(), 60_000_000, true),
+            (current.as_str(), 120_000_000, true),
+            (current.as_str(), 120_000_001, false),
+            (current.as_str(), -1, false),
+            (NO_TRACK, 60_000_000, false),
+            ("/org/jorik/track/t0000000000000000", 60_000_000, false),
+        ];
+        for (track_id, position, expected) in cases {
+            assert_eq!(accepts_position(&now, track_id, position), expected, "{} {}", track_id, position);
+        }
+    }
+
+    #[test]
+    fn seeking_past_the_end_skips() {
+        let now = now(false, None);
+        assert_eq!(seek_target(&now, 30_000_000, 10_000_000), Some(40_000_000));
+        assert_eq!(seek_target(&now, 30_000_000, -40_000_000), Some(-10_000_000));
+        assert_eq!(seek_target(&now, 30_000_000, 90_000_000), Some(120_000_000));
+        assert_eq!(seek_target(&now, 30_000_000, 90_000_001), None);
+    }
+}
@@ -0,0 +1,269 @@
+//! Per-provider URL normalisation for queries sent to `play`.
+//!
+//! Pasted links often carry share/tracking parameters, mobile or music
+//! subdomains, or app URIs (`spotify:track:...`). Each provider in [`RULES`]
+//! lists the hosts it matches, the canonical host, the query parameters worth
+//! keeping and an optional path rewrite. Unknown hosts only lose the common
+//! tracking parameters. Non-URL queries are passed through untouched.
+
+use reqwest::Url;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Turn normalisation off for this process (`--no-normalize`).
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+struct Rule {
+    /// Hosts this rule applies to, compared without a leading `www.`
+    hosts: &'static [&'static str],
+    canonical_host: &'static str,
+    /// Query parameters to keep; everything else is dropped
+    keep: &'static [&'static str],
+    rewrite: Option<fn(&mut Url)>,
+}
+
+const RULES: &[Rule] = &[
+    Rule {
+        hosts: &["youtube.com", "m.youtube.com", "music.youtube.com"],
+        canonical_host: "www.youtube.com",
+        keep: &["v", "t", "list"],
+        rewrite: Some(youtube_path),
+    },
+    Rule {
+        hosts: &["youtu.be"],
+        canonical_host: "www.youtube.com",
+        keep: &["v", "t", "list"],
+        rewrite: Some(youtu_be_path),
+    },
+    Rule {
+        hosts: &["soundcloud.com", "m.soundcloud.com"],
+        canonical_host: "soundcloud.com",
+        keep: &[],
+        rewrite: None,
+    },
+    Rule {
+        hosts: &["open.spotify.com", "play.spotify.com"],
+        canonical_host: "open.spotify.com",
+        keep: &[],
+        rewrite: Some(spotify_path),
+    },
+];
+
+/// Parameters dropped from URLs of hosts without a rule.
+const TRACKING_PARAMS: &[&str] = &["si", "fbclid", "gclid", "igshid", "mc_cid", "mc_eid"];
+
+const SPOTIFY_TYPES: &[&str] = &["track", "album", "playlist", "artist", "episode", "show"];
+
+pub fn normalize(input: &str) -> String {
+    let trimmed = input.trim();
+    if let Some(url) = convert_uri(trimmed) {
+        return url;
+    }
+    let Ok(mut url) = Url::parse(trimmed) else {
+        return input.to_string();
+    };
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return input.to_string();
+    }
+
+    let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host).to_string();
+    let rule = RULES.iter().find(|r| r.hosts.contains(&host.as_str()));
+
+    if let Some(rule) = rule {
+        if let Some(rewrite) = rule.rewrite {
+            rewrite(&mut url);
+        }
+        let _ = url.set_host(Some(rule.canonical_host));
+        let _ = url.set_scheme("https");
+        url.set_fragment(None);
+    }
+
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, v)| match rule {
+            Some(rule) => rule.keep.contains(&k.as_ref()) && !is_radio_list(k, v),
+            None => !TRACKING_PARAMS.contains(&k.as_ref()) && !k.starts_with("utm_"),
+        })
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    if pairs.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    url.to_string()
+}
+
+/// App URI schemes that have a web equivalent.
+fn convert_uri(input: &str) -> Option<String> {
+    if let Some(rest) = input.strip_prefix("spotify:") {
+        // spotify:track:ID, or the legacy spotify:user:NAME:playlist:ID
+        let parts: Vec<&str> = rest.split(':').collect();
+        let (kind, id) = match parts.as_slice() {
+            [kind, id] => (*kind, *id),
+            ["user", _, kind, id] => (*kind, *id),
+            _ => return None,
+        };
+        if !SPOTIFY_TYPES.contains(&kind) || id.is_empty() {
+            return None;
+        }
+        return Some(format!("https://open.spotify.com/{}/{}", kind, id));
+    }
+    if let Some(id) = input.strip_prefix("vnd.youtube:") {
+        let id = id.split(['?', '&']).next().unwrap_or_default();
+        if !id.is_empty() {
+            return Some(format!("https://www.youtube.com/watch?v={}", id));
+        }
+    }
+    None
+}
+
+/// Auto-generated "mix" playlists (`list=RD...`) only make `play` enqueue a
+/// radio instead of the linked video.
+fn is_radio_list(key: &str, value: &str) -> bool {
+    key == "list" && value.starts_with("RD")
+}
+
+/// `/shorts/ID`, `/embed/ID` and `/live/ID` become `/watch?v=ID`.
+fn youtube_path(url: &mut Url) {
+    let id = {
+        let mut segments = url.path_segments().into_iter().flatten();
+        match (segments.next(), segments.next()) {
+            (Some("shorts" | "embed" | "live"), Some(id)) if !id.is_empty() => id.to_string(),
+            _ => return,
+        }
+    };
+    url.set_path("/watch");
+    prepend_video_id(url, &id);
+}
+
+/// `youtu.be/ID` becomes `/watch?v=ID`.
+fn youtu_be_path(url: &mut Url) {
+    let id = url.path().trim_matches('/').to_string();
+    if id.is_empty() {
+        return;
+    }
+    url.set_path("/watch");
+    prepend_video_id(url, &id);
+}
+
+fn prepend_video_id(url: &mut Url, id: &str) {
+    let rest: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| k != "v")
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    url.query_pairs_mut()
+        .clear()
+        .append_pair("v", id)
+        .extend_pairs(rest);
+}
+
+/// Drop the `/intl-xx` locale prefix and the `/embed` prefix.
+fn spotify_path(url: &mut Url) {
+    let segments: Vec<String> = url
+        .path_segments()
+        .into_iter()
+        .flatten()
+        .skip_while(|s| s.starts_with("intl-") || *s == "embed")
+        .map(str::to_string)
+        .collect();
+    url.set_path(&segments.join("/"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_queries() {
+        let cases = [
+            // plain queries and search prefixes are untouched
+            ("never gonna give you up", "never gonna give you up"),
+            ("ytsearch:lofi", "ytsearch:lofi"),
+            ("  lofi  ", "  lofi  "),
+            // youtube
+            (
+                "https://youtu.be/dQw4w9WgXcQ",
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            ),
+            (
+                "https://youtu.be/dQw4w9WgXcQ?si=abc&t=42",
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42",
+            ),
+            (
+                "https://music.youtube.com/watch?v=abc&list=RDAMVMabc&index=2",
+                "https://www.youtube.com/watch?v=abc",
+            ),
+            (
+                "https://m.youtube.com/watch?v=abc&feature=share&pp=ygU",
+                "https://www.youtube.com/watch?v=abc",
+            ),
+            (
+                "https://www.youtube.com/watch?v=abc&list=PLxyz&start_radio=1",
+                "https://www.youtube.com/watch?v=abc&list=PLxyz",
+            ),
+            (
+                "https://youtube.com/playlist?list=PLxyz&si=abc",
+                "https://www.youtube.com/playlist?list=PLxyz",
+            ),
+            (
+                "https://youtube.com/shorts/abc?feature=share",
+                "https://www.youtube.com/watch?v=abc",
+            ),
+            (
+                "https://www.youtube.com/embed/abc",
+                "https://www.youtube.com/watch?v=abc",
+            ),
+            ("vnd.youtube:abc", "https://www.youtube.com/watch?v=abc"),
+            // spotify
+            (
+                "spotify:track:2RQWB4Asy1rjZL4IUcJ7kn",
+                "https://open.spotify.com/track/2RQWB4Asy1rjZL4IUcJ7kn",
+            ),
+            (
+                "spotify:user:someone:playlist:37i9dQ",
+                "https://open.spotify.com/playlist/37i9dQ",
+            ),
+            ("spotify:bogus:123", "spotify:bogus:123"),
+            (
+                "https://open.spotify.com/track/2RQWB4Asy1rjZL4IUcJ7kn?si=abc123",
+                "https://open.spotify.com/track/2RQWB4Asy1rjZL4IUcJ7kn",
+            ),
+            (
+                "https://open.spotify.com/intl-de/track/abc?si=x&context=y",
+                "https://open.spotify.com/track/abc",
+            ),
+            (
+                "https://open.spotify.com/embed/album/abc",
+                "https://open.spotify.com/album/abc",
+            ),
+            // soundcloud
+            (
+                "https://soundcloud.com/artist/track?in=artist/sets/album&utm_source=clipboard",
+                "https://soundcloud.com/artist/track",
+            ),
+            (
+                "https://m.soundcloud.com/artist/track/s-SECRET?si=abc",
+                "https://soundcloud.com/artist/track/s-SECRET",
+            ),
+            // other hosts only lose tracking parameters
+            (
+                "https://example.com/a.mp3?utm_source=x&id=5&fbclid=y",
+                "https://example.com/a.mp3?id=5",
+            ),
+            ("https://example.com/a.mp3", "https://example.com/a.mp3"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(normalize(input), expected, "input: {}", input);
+        }
+    }
+}
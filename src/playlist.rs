@@ -0,0 +1,238 @@
+//! Playlist file import for `jorik play --file` / `jorik play -`.
+//!
+//! Supported formats are M3U/M3U8, PLS, JSON arrays and newline-delimited
+//! text. The format is picked from the file extension and falls back to
+//! sniffing the content (stdin has no extension).
+
+use crate::api::{PlayPayload, clean_query, fetch_audio};
+use crate::search::{SearchSource, resolve_query};
+use anyhow::{Context, Result, bail};
+use colored::Colorize;
+use futures_util::{StreamExt, stream};
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::Client;
+use serde_json::Value;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Read playlist entries from `path`, or from stdin when `path` is `-`.
+pub fn read_entries(path: &Path) -> Result<Vec<String>> {
+    let (content, ext) = if path.as_os_str() == "-" {
+        let mut buf = String::new();
+        io::stdin()
+            .read_to_string(&mut buf)
+            .context("reading playlist from stdin")?;
+        (buf, None)
+    } else {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("reading playlist {}", path.display()))?;
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        (content, ext)
+    };
+
+    let entries = parse(&content, ext.as_deref())
+        .with_context(|| format!("parsing playlist {}", path.display()))?;
+    if entries.is_empty() {
+        bail!("playlist {} contains no entries", path.display());
+    }
+    Ok(entries)
+}
+
+pub fn parse(content: &str, ext: Option<&str>) -> Result<Vec<String>> {
+    let trimmed = content.trim_start_matches('\u{feff}').trim_start();
+    match ext {
+        Some("json") => parse_json(trimmed),
+        Some("pls") => Ok(parse_pls(trimmed)),
+        Some("m3u") | Some("m3u8") | Some("txt") => Ok(parse_lines(trimmed)),
+        _ if trimmed.starts_with('[') && !trimmed.to_ascii_lowercase().starts_with("[playlist]") => {
+            parse_json(trimmed)
+        }
+        _ if trimmed.to_ascii_lowercase().starts_with("[playlist]") => Ok(parse_pls(trimmed)),
+        _ => Ok(parse_lines(trimmed)),
+    }
+}
+
+/// M3U and plain text: one entry per line, `#` lines are comments/directives.
+fn parse_lines(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// PLS: `FileN=` keys, ordered by N.
+fn parse_pls(content: &str) -> Vec<String> {
+    let mut entries: Vec<(usize, String)> = content
+        .lines()
+        .filter_map(|l| {
+            let (key, value) = l.trim().split_once('=')?;
+            let index = key.trim().strip_prefix("File")?.parse().ok()?;
+            let value = value.trim();
+            (!value.is_empty()).then(|| (index, value.to_string()))
+        })
+        .collect();
+    entries.sort_by_key(|(i, _)| *i);
+    entries.into_iter().map(|(_, v)| v).collect()
+}
+
+/// JSON: an array of strings, or of objects with a `uri`, `url` or `query` field.
+fn parse_json(content: &str) -> Result<Vec<String>> {
+    let value: Value = serde_json::from_str(content).context("invalid JSON")?;
+    let items = value.as_array().context("expected a JSON array")?;
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| match item {
+            Value::String(s) => Ok(s.clone()),
+            Value::Object(obj) => ["uri", "url", "query"]
+                .iter()
+                .find_map(|k| obj.get(*k).and_then(|v| v.as_str()))
+                .map(str::to_string)
+                .with_context(|| format!("entry {} has no uri, url or query", i + 1)),
+            _ => bail!("entry {} is not a string or object", i + 1),
+        })
+        .collect()
+}
+
+/// Build one play payload per entry, using `template` for the shared fields.
+/// Every entry goes through `clean_query` and then, as a single `play` query
+/// does, `resolve_query` with `source` (so links aren't searched for).
+pub fn payloads(
+    entries: Vec<String>,
+    template: &PlayPayload,
+    source: Option<SearchSource>,
+) -> Vec<PlayPayload> {
+    entries
+        .into_iter()
+        .map(|entry| PlayPayload {
+            query: resolve_query(&clean_query(&entry), source),
+            ..template.clone()
+        })
+        .collect()
+}
+
+/// Enqueue every payload with at most `concurrency` requests in flight.
+/// Returns the number of failed entries.
+pub async fn enqueue_all(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    payloads: Vec<PlayPayload>,
+    concurrency: usize,
+) -> usize {
+    let total = payloads.len();
+    let pb = ProgressBar::new(total as u64);
+    pb.set_style(
+        ProgressStyle::with_template("{spinner:.cyan} [{bar:30.cyan/blue}] {pos}/{len} {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("=> "),
+    );
+
+    let results: Vec<bool> = stream::iter(payloads)
+        .map(|payload| {
+            let pb = pb.clone();
+            async move {
+                let entry = payload.query.clone();
+                pb.set_message(entry.clone());
+                let result = fetch_audio(client, base_url, token, &payload).await;
+                if let Err(e) = &result {
+                    pb.suspend(|| eprintln!("{} {}: {:#}", "✘".red(), entry, e));
+                }
+                pb.inc(1);
+                result.is_ok()
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+    pb.finish_and_clear();
+
+    let failed = results.iter().filter(|ok| !**ok).count();
+    println!(
+        "{} Queued {} of {} entries{}",
+        if failed == 0 { "✔".green() } else { "⚠".yellow() },
+        (total - failed).to_string().bold(),
+        total,
+        if failed > 0 {
+            format!(", {} failed", failed.to_string().red().bold())
+        } else {
+            String::new()
+        }
+    );
+    failed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_format() {
+        let expected = ["https://youtu.be/a", "lofi beats"];
+        let cases = [
+            (
+                Some("m3u8"),
+                "\u{feff}#EXTM3U\n#EXTINF:123,A\nhttps://youtu.be/a\n\n  lofi beats  \n",
+            ),
+            (Some("txt"), "https://youtu.be/a\r\nlofi beats\r\n"),
+            (
+                Some("pls"),
+                "[playlist]\nFile2=lofi beats\nTitle1=A\nFile1=https://youtu.be/a\nLength1=-1\n",
+            ),
+            (
+                Some("json"),
+                r#"[{"uri": "https://youtu.be/a", "title": "A"}, {"query": "lofi beats"}]"#,
+            ),
+            // sniffed from the content, as for stdin
+            (None, r#"["https://youtu.be/a", {"url": "lofi beats"}]"#),
+            (None, "[Playlist]\nFile1=https://youtu.be/a\nFile2=lofi beats\n"),
+            (None, "#EXTM3U\nhttps://youtu.be/a\nlofi beats"),
+        ];
+        for (ext, content) in cases {
+            assert_eq!(parse(content, ext).unwrap(), expected, "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_json() {
+        for content in [r#"{"uri": "a"}"#, r#"["a", 5]"#, r#"[{"title": "A"}]"#, "[oops"] {
+            assert!(parse(content, Some("json")).is_err(), "{}", content);
+        }
+    }
+
+    #[test]
+    fn normalizes_entries_before_resolving_them() {
+        let template = PlayPayload {
+            action: "play",
+            guild_id: None,
+            channel_id: None,
+            query: String::new(),
+            user_id: None,
+            requested_by: None,
+            avatar_url: None,
+            position: None,
+        };
+        let entries = ["spotify:track:abc", "https://youtu.be/xyz?si=1", "lofi", "scsearch:rain"]
+            .map(String::from)
+            .to_vec();
+        let queries: Vec<String> = payloads(entries, &template, Some(SearchSource::Youtube))
+            .into_iter()
+            .map(|p| p.query)
+            .collect();
+        assert_eq!(
+            queries,
+            [
+                "https://open.spotify.com/track/abc",
+                "https://www.youtube.com/watch?v=xyz",
+                "ytsearch:lofi",
+                "scsearch:rain",
+            ]
+        );
+    }
+}
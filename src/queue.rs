@@ -0,0 +1,256 @@
+//! Queue snapshots for `jorik queue export` / `jorik queue import`.
+
+use crate::api::{QueuePayload, fetch_audio};
+use crate::playlist;
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// Number of tracks requested per `queue` call while exporting.
+const PAGE_SIZE: usize = 50;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct QueueEntry {
+    pub uri: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default, alias = "duration", alias = "durationMs", alias = "length")]
+    pub duration_ms: u64,
+    #[serde(default, alias = "requester", alias = "requestedBy")]
+    pub requested_by: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    M3u,
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    /// Guess the format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "m3u" | "m3u8" => Some(ExportFormat::M3u),
+            "json" => Some(ExportFormat::Json),
+            "csv" => Some(ExportFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Number of upcoming tracks, not counting the one playing.
+pub async fn count(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    guild_id: Option<String>,
+    user_id: Option<String>,
+) -> Result<usize> {
+    let payload = QueuePayload {
+        action: "queue",
+        guild_id,
+        user_id,
+        limit: 1,
+        offset: 0,
+    };
+    let json = fetch_audio(client, base_url, token, &payload).await?;
+    let queue = json.get("queue").unwrap_or(&json);
+    let upcoming = queue.get("upcoming").and_then(|v| v.as_array()).map_or(0, Vec::len);
+    Ok(queue
+        .get("total_upcoming")
+        .and_then(|v| v.as_u64())
+        .map_or(upcoming, |t| t as usize))
+}
+
+/// Fetch the current track and every upcoming track, paging through the queue.
+pub async fn fetch_all(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    guild_id: Option<String>,
+    user_id: Option<String>,
+) -> Result<Vec<QueueEntry>> {
+    let (current, upcoming) = fetch_split(client, base_url, token, guild_id, user_id).await?;
+    Ok(current.into_iter().chain(upcoming).collect())
+}
+
+/// Like [`fetch_all`], keeping the current track apart from the upcoming ones.
+pub async fn fetch_split(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    guild_id: Option<String>,
+    user_id: Option<String>,
+) -> Result<(Option<QueueEntry>, Vec<QueueEntry>)> {
+    let mut current_entry = None;
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    loop {
+        let payload = QueuePayload {
+            action: "queue",
+            guild_id: guild_id.clone(),
+            user_id: user_id.clone(),
+            limit: PAGE_SIZE,
+            offset,
+        };
+        let json = fetch_audio(client, base_url, token, &payload).await?;
+        let queue = json.get("queue").unwrap_or(&json);
+
+        if offset == 0
+            && let Some(current) = queue.get("current").filter(|c| c.is_object())
+        {
+            current_entry = Some(parse_entry(current)?);
+        }
+
+        let upcoming = queue
+            .get("upcoming")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default();
+        let total = queue
+            .get("total_upcoming")
+            .and_then(|v| v.as_u64())
+            .map(|t| t as usize);
+
+        offset += upcoming.len();
+        for item in &upcoming {
+            entries.push(parse_entry(item)?);
+        }
+
+        // Without `total_upcoming`, a short page means we reached the end.
+        let done = match total {
+            Some(total) => offset >= total,
+            None => upcoming.len() < PAGE_SIZE,
+        };
+        if upcoming.is_empty() || done {
+            break;
+        }
+    }
+    Ok((current_entry, entries))
+}
+
+fn parse_entry(value: &Value) -> Result<QueueEntry> {
+    serde_json::from_value(value.clone()).context("queue entry without a uri")
+}
+
+pub fn render(entries: &[QueueEntry], format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(entries)? + "\n"),
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            for entry in entries {
+                writer.serialize(entry)?;
+            }
+            Ok(String::from_utf8(writer.into_inner()?)?)
+        }
+        ExportFormat::M3u => {
+            let mut out = String::from("#EXTM3U\n");
+            for entry in entries {
+                let name = if entry.author.is_empty() {
+                    entry.title.clone()
+                } else {
+                    format!("{} - {}", entry.author, entry.title)
+                };
+                out.push_str(&format!("#EXTINF:{},{}\n", entry.duration_ms / 1000, name));
+                out.push_str(&format!("{}\n", entry.uri));
+            }
+            Ok(out)
+        }
+    }
+}
+
+/// Read an exported queue. JSON and CSV keep requesters; anything else is
+/// treated as a playlist file and yields URIs only.
+pub fn read(path: &Path) -> Result<Vec<QueueEntry>> {
+    let entries = match ExportFormat::from_path(path) {
+        Some(ExportFormat::Json) => {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("reading {}", path.display()))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("parsing {}", path.display()))?
+        }
+        Some(ExportFormat::Csv) => {
+            let mut reader = csv::Reader::from_path(path)
+                .with_context(|| format!("reading {}", path.display()))?;
+            reader
+                .deserialize()
+                .collect::<Result<Vec<QueueEntry>, _>>()
+                .with_context(|| format!("parsing {}", path.display()))?
+        }
+        _ => playlist::read_entries(path)?
+            .into_iter()
+            .map(|uri| QueueEntry {
+                uri,
+                title: String::new(),
+                author: String::new(),
+                duration_ms: 0,
+                requested_by: None,
+            })
+            .collect(),
+    };
+    if entries.is_empty() {
+        bail!("{} contains no tracks", path.display());
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entries() -> Vec<QueueEntry> {
+        serde_json::from_value(json!([
+            {"uri": "https://youtu.be/a", "title": "A, \"live\"", "author": "X", "duration": 61000,
+             "requester": "bob"},
+            {"uri": "https://youtu.be/b", "title": "B"},
+        ]))
+        .unwrap()
+    }
+
+    /// Render to a temporary file and read it back.
+    fn round_trip(format: ExportFormat, ext: &str) -> (String, Vec<QueueEntry>) {
+        let rendered = render(&entries(), format).unwrap();
+        let path = std::env::temp_dir().join(format!("jorik-queue-{}.{}", std::process::id(), ext));
+        fs::write(&path, &rendered).unwrap();
+        let read = read(&path);
+        let _ = fs::remove_file(&path);
+        (rendered, read.unwrap())
+    }
+
+    #[test]
+    fn round_trips_json_and_csv() {
+        for (format, ext) in [(ExportFormat::Json, "json"), (ExportFormat::Csv, "csv")] {
+            let (_, read) = round_trip(format, ext);
+            assert_eq!(
+                serde_json::to_value(&read).unwrap(),
+                serde_json::to_value(entries()).unwrap(),
+                "{}",
+                ext
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_m3u_uris() {
+        let (rendered, read) = round_trip(ExportFormat::M3u, "m3u");
+        assert_eq!(
+            rendered,
+            concat!(
+                "#EXTM3U\n",
+                "#EXTINF:61,X - A, \"live\"\nhttps://youtu.be/a\n",
+                "#EXTINF:0,B\nhttps://youtu.be/b\n",
+            )
+        );
+        let uris: Vec<&str> = read.iter().map(|e| e.uri.as_str()).collect();
+        assert_eq!(uris, ["https://youtu.be/a", "https://youtu.be/b"]);
+    }
+}
@@ -0,0 +1,132 @@
+//! Scripts for `jorik exec`: one CLI command per line, `#` comments, and
+//! `wait <duration>` pauses next to the usual `wait <condition>`.
+//!
+//! ```text
+//! # warm-up
+//! loop queue
+//! play "lofi hip hop" --source sc
+//! wait track-start --timeout 30s
+//! wait 10s
+//! filter nightcore
+//! ```
+
+use anyhow::{Context, Result, bail};
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+
+pub enum Step {
+    /// `wait 10s`
+    Sleep(Duration),
+    /// Arguments as they would follow `jorik` on the command line
+    Command(Vec<String>),
+}
+
+pub struct Line {
+    /// 1-based line number, for reporting
+    pub number: usize,
+    pub text: String,
+    pub step: Step,
+}
+
+/// Read a script from `path`, or from stdin for `-` or no path.
+pub fn read(path: Option<&Path>) -> Result<String> {
+    match path.filter(|p| p.as_os_str() != "-") {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("reading {}", path.display())),
+        None => {
+            let mut source = String::new();
+            std::io::stdin()
+                .read_to_string(&mut source)
+                .context("reading script from stdin")?;
+            Ok(source)
+        }
+    }
+}
+
+/// Split a script into steps, skipping blank lines and comments. A leading
+/// `jorik` is allowed so lines can be pasted from a shell.
+pub fn parse(source: &str) -> Result<Vec<Line>> {
+    let mut lines = Vec::new();
+    for (i, text) in source.lines().enumerate() {
+        let number = i + 1;
+        let Some(mut words) = shlex::split(text) else {
+            bail!("line {}: unbalanced quotes", number);
+        };
+        if words.first().is_some_and(|w| w == "jorik") {
+            words.remove(0);
+        }
+        if words.is_empty() {
+            continue;
+        }
+        let step = match words.as_slice() {
+            [wait, duration] if wait == "wait" => match humantime::parse_duration(duration) {
+                Ok(duration) => Step::Sleep(duration),
+                Err(_) => Step::Command(words),
+            },
+            _ => Step::Command(words),
+        };
+        lines.push(Line {
+            number,
+            text: text.trim().to_string(),
+            step,
+        });
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(source: &str) -> Vec<(usize, String)> {
+        parse(source)
+            .unwrap()
+            .into_iter()
+            .map(|line| {
+                let step = match line.step {
+                    Step::Sleep(duration) => format!("sleep {}ms", duration.as_millis()),
+                    Step::Command(words) => words.join("|"),
+                };
+                (line.number, step)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_scripts() {
+        let source = "\
+# warm-up
+
+loop queue
+jorik play \"lofi hip hop\" --source sc
+  wait track-start --timeout 30s
+wait 1m30s
+wait 250ms   # comment
+filter nightcore
+";
+        assert_eq!(
+            steps(source),
+            [
+                (3, "loop|queue".to_string()),
+                (4, "play|lofi hip hop|--source|sc".to_string()),
+                (5, "wait|track-start|--timeout|30s".to_string()),
+                (6, "sleep 90000ms".to_string()),
+                (7, "sleep 250ms".to_string()),
+                (8, "filter|nightcore".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_wait_conditions_as_commands() {
+        assert_eq!(steps("wait playing"), [(1, "wait|playing".to_string())]);
+        assert_eq!(steps("jorik"), []);
+    }
+
+    #[test]
+    fn rejects_unbalanced_quotes() {
+        let err = parse("play lofi\nplay \"never ending").err().unwrap();
+        assert_eq!(err.to_string(), "line 2: unbalanced quotes");
+    }
+}
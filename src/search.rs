@@ -0,0 +1,232 @@
+//! Track search: source prefixes, result parsing and the interactive picker.
+//!
+//! The server resolves `ytsearch:`-style queries itself; the CLI only chooses
+//! the prefix and lets the user pick one of the returned tracks so that the
+//! exact URI gets enqueued instead of whatever the server ranks first.
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{self, Write};
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum SearchSource {
+    /// YouTube
+    #[value(name = "yt", alias = "youtube")]
+    #[serde(rename = "yt")]
+    Youtube,
+    /// SoundCloud
+    #[value(name = "sc", alias = "soundcloud")]
+    #[serde(rename = "sc")]
+    Soundcloud,
+    /// Spotify
+    #[value(name = "sp", alias = "spotify")]
+    #[serde(rename = "sp")]
+    Spotify,
+    /// Apple Music
+    #[value(name = "am", alias = "applemusic")]
+    #[serde(rename = "am")]
+    AppleMusic,
+}
+
+impl SearchSource {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            SearchSource::Youtube => "ytsearch:",
+            SearchSource::Soundcloud => "scsearch:",
+            SearchSource::Spotify => "spsearch:",
+            SearchSource::AppleMusic => "amsearch:",
+        }
+    }
+
+    /// The short name used in `yt:`-style query prefixes.
+    pub fn short_name(self) -> &'static str {
+        match self {
+            SearchSource::Youtube => "yt",
+            SearchSource::Soundcloud => "sc",
+            SearchSource::Spotify => "sp",
+            SearchSource::AppleMusic => "am",
+        }
+    }
+}
+
+/// Split a `yt:`/`sc:`/`sp:`/`am:` prefix off a query.
+pub fn split_source(query: &str) -> (Option<SearchSource>, &str) {
+    let query = query.trim();
+    if let Some((prefix, rest)) = query.split_once(':')
+        && !rest.starts_with("//")
+        && let Some(source) = SearchSource::value_variants()
+            .iter()
+            .find(|s| s.short_name().eq_ignore_ascii_case(prefix))
+    {
+        return (Some(*source), rest.trim_start());
+    }
+    (None, query)
+}
+
+/// Build the query sent to the server. URLs and already-prefixed
+/// (`ytsearch:`) queries are passed through untouched. Otherwise an inline
+/// `yt:`-style prefix wins over `source`; with neither the server default is
+/// used.
+pub fn resolve_query(query: &str, source: Option<SearchSource>) -> String {
+    let (inline, rest) = split_source(query);
+    let prefixed = rest
+        .split_once(':')
+        .is_some_and(|(p, _)| p.ends_with("search"));
+    if inline.is_none() && (rest.contains("://") || prefixed) {
+        return rest.to_string();
+    }
+    match inline.or(source) {
+        Some(source) => format!("{}{}", source.prefix(), rest),
+        None => rest.to_string(),
+    }
+}
+
+/// Like [`resolve_query`], always searching `source` when the query names none.
+pub fn search_query(query: &str, source: SearchSource) -> String {
+    resolve_query(query, Some(source))
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SearchTrack {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub author: String,
+    pub uri: String,
+    #[serde(default, alias = "duration", alias = "length", alias = "durationMs")]
+    pub duration_ms: u64,
+}
+
+/// Extract the candidate tracks from a `search` response. Accepts `tracks` at
+/// the top level or nested under `data`.
+pub fn parse_tracks(json: &Value) -> Vec<SearchTrack> {
+    json.get("tracks")
+        .or_else(|| json.get("data").and_then(|d| d.get("tracks")))
+        .and_then(|v| v.as_array())
+        .map(|tracks| {
+            tracks
+                .iter()
+                .filter_map(|t| serde_json::from_value(t.clone()).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn format_duration(ms: u64) -> String {
+    let secs = ms / 1000;
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+pub fn print_results(tracks: &[SearchTrack]) {
+    for (i, track) in tracks.iter().enumerate() {
+        println!(
+            "  {:>2}. {} {} {}",
+            (i + 1).to_string().cyan(),
+            track.title.bold(),
+            format!("— {}", track.author).dimmed(),
+            format!("[{}]", format_duration(track.duration_ms)).dimmed()
+        );
+    }
+}
+
+/// Resolve `--select N` (1-based) to an index, or ask on stdin.
+/// Returns `None` when the user cancels the prompt.
+pub fn pick(tracks: &[SearchTrack], select: Option<usize>) -> Result<Option<usize>> {
+    if let Some(n) = select {
+        if n == 0 || n > tracks.len() {
+            bail!("--select {} is out of range (1-{})", n, tracks.len());
+        }
+        return Ok(Some(n - 1));
+    }
+
+    loop {
+        print!("Pick a track [1-{}, q to cancel]: ", tracks.len());
+        io::stdout().flush().ok();
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line).context("reading selection")? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim();
+        if line.is_empty() || line.eq_ignore_ascii_case("q") {
+            return Ok(None);
+        }
+        match line.parse::<usize>() {
+            Ok(n) if (1..=tracks.len()).contains(&n) => return Ok(Some(n - 1)),
+            _ => println!("{} Enter a number between 1 and {}", "✘".red(), tracks.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn resolves_queries() {
+        use SearchSource::*;
+        let cases = [
+            ("lofi", None, "lofi"),
+            ("lofi", Some(Youtube), "ytsearch:lofi"),
+            // an inline prefix wins over the default source
+            ("sc: lofi", Some(Youtube), "scsearch:lofi"),
+            ("AM:lofi", None, "amsearch:lofi"),
+            // links and explicit search prefixes pass through
+            ("https://youtu.be/a", Some(Spotify), "https://youtu.be/a"),
+            ("ytsearch:lofi", Some(Soundcloud), "ytsearch:lofi"),
+            // not a known prefix
+            ("re: zero", Some(Youtube), "ytsearch:re: zero"),
+        ];
+        for (query, source, expected) in cases {
+            assert_eq!(resolve_query(query, source), expected, "{:?} {:?}", query, source);
+        }
+    }
+
+    #[test]
+    fn parses_search_results() {
+        let track = json!({
+            "title": "A", "author": "B", "uri": "https://youtu.be/a",
+            "length": 61000,
+        });
+        let cases = [
+            (json!({"tracks": [track]}), 1),
+            (json!({"data": {"tracks": [track, {"title": "no uri"}]}}), 1),
+            (json!({"tracks": "nope"}), 0),
+            (json!({"error": "no_results"}), 0),
+        ];
+        for (json, expected) in cases {
+            assert_eq!(parse_tracks(&json).len(), expected, "{}", json);
+        }
+        let parsed = &parse_tracks(&json!({"tracks": [track]}))[0];
+        assert_eq!(
+            (parsed.title.as_str(), parsed.author.as_str(), parsed.duration_ms),
+            ("A", "B", 61000)
+        );
+    }
+
+    #[test]
+    fn formats_durations() {
+        let cases = [(0, "0:00"), (61_000, "1:01"), (3_599_999, "59:59"), (3_723_000, "1:02:03")];
+        for (ms, expected) in cases {
+            assert_eq!(format_duration(ms), expected);
+        }
+    }
+
+    #[test]
+    fn picks_with_select() {
+        let tracks = parse_tracks(&json!({"tracks": [
+            {"uri": "a"}, {"uri": "b"}, {"uri": "c"},
+        ]}));
+        assert_eq!(pick(&tracks, Some(1)).unwrap(), Some(0));
+        assert_eq!(pick(&tracks, Some(3)).unwrap(), Some(2));
+        assert!(pick(&tracks, Some(0)).is_err());
+        assert!(pick(&tracks, Some(4)).is_err());
+    }
+}
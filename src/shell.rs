@@ -0,0 +1,210 @@
+//! `jorik shell`: line editing, history and tab completion for the
+//! interactive prompt, plus the now-playing notices printed above it.
+//!
+//! Completion walks the same clap definition the CLI parses with, so new
+//! subcommands and flags show up without touching this file. Values that
+//! live in local data (presets, favorites, aliases, guilds) come from
+//! [`complete`].
+
+use crate::complete::{self, Source};
+use crate::ws::{Subscription, Update};
+use clap::{Arg, Command};
+use colored::Colorize;
+use rustyline::completion::{Completer, Pair};
+use rustyline::{ExternalPrinter, Helper, Highlighter, Hinter, Validator};
+
+#[derive(Helper, Highlighter, Hinter, Validator)]
+pub struct ShellHelper {
+    root: Command,
+    profile: String,
+}
+
+impl ShellHelper {
+    pub fn new(root: Command, profile: String) -> Self {
+        ShellHelper { root, profile }
+    }
+
+    /// Candidates for the word being typed after `words`.
+    fn candidates(&self, words: &[&str], word: &str) -> Vec<String> {
+        let mut cmd = &self.root;
+        let mut path: Vec<&str> = Vec::new();
+        let mut positionals = 0;
+        let mut pending: Option<&Arg> = None;
+        for w in words {
+            if pending.take().is_some() {
+                continue;
+            }
+            if let Some(long) = w.strip_prefix("--") {
+                if !long.contains('=') {
+                    pending = self.find_long(cmd, long).filter(|a| a.get_action().takes_values());
+                }
+                continue;
+            }
+            if w.starts_with('-') {
+                continue;
+            }
+            if positionals == 0
+                && let Some(sub) = cmd.find_subcommand(w)
+            {
+                cmd = sub;
+                path.push(sub.get_name());
+                continue;
+            }
+            positionals += 1;
+        }
+
+        if let Some(arg) = pending {
+            return self.values(&path, arg);
+        }
+        if word.starts_with('-') {
+            return cmd
+                .get_arguments()
+                .chain(self.root.get_arguments().filter(|a| a.is_global_set()))
+                .filter(|a| !a.is_hide_set())
+                .filter_map(|a| a.get_long().map(|l| format!("--{}", l)))
+                .collect();
+        }
+        if path == ["play"] && word.starts_with("fav:") {
+            return Source::Favorites
+                .values(&self.profile)
+                .into_iter()
+                .map(|f| format!("fav:{}", f))
+                .collect();
+        }
+
+        let mut out = Vec::new();
+        if positionals == 0 {
+            out.extend(
+                cmd.get_subcommands()
+                    .filter(|s| !s.is_hide_set())
+                    .map(|s| s.get_name().to_string()),
+            );
+            if path.is_empty() {
+                out.extend(Source::Aliases.values(&self.profile));
+            }
+        }
+        if let Some(arg) = cmd.get_positionals().nth(positionals) {
+            out.extend(self.values(&path, arg));
+        }
+        out
+    }
+
+    /// Look up `--long` on `cmd`, falling back to the global flags.
+    fn find_long<'a>(&'a self, cmd: &'a Command, long: &str) -> Option<&'a Arg> {
+        cmd.get_arguments()
+            .chain(self.root.get_arguments())
+            .find(|a| a.get_long() == Some(long))
+    }
+
+    /// Values for `arg` of the subcommand at `path`.
+    fn values(&self, path: &[&str], arg: &Arg) -> Vec<String> {
+        match complete::source_for(&path.join(" "), arg.get_id().as_str()) {
+            Some(source) => source.values(&self.profile),
+            None => arg
+                .get_possible_values()
+                .iter()
+                .filter(|v| !v.is_hide_set())
+                .map(|v| v.get_name().to_string())
+                .collect(),
+        }
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &before[start..];
+        let mut words: Vec<&str> = before[..start].split_whitespace().collect();
+        if words.first() == Some(&"jorik") {
+            words.remove(0);
+        }
+        let mut candidates = self.candidates(&words, word);
+        candidates.retain(|c| c.starts_with(word));
+        candidates.sort();
+        candidates.dedup();
+        let pairs = candidates
+            .into_iter()
+            .map(|c| Pair {
+                display: c.clone(),
+                replacement: c,
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+/// Print a line above the prompt whenever a new track starts, for as long as
+/// the shell runs.
+pub fn spawn_notices<P>(mut printer: P, mut subscription: Subscription)
+where
+    P: ExternalPrinter + Send + 'static,
+{
+    tokio::spawn(async move {
+        while let Some(update) = subscription.next().await {
+            let Update::Event { event, .. } = update else {
+                continue;
+            };
+            if event.event_type != "track_start"
+                || event
+                    .guild_id
+                    .as_deref()
+                    .is_some_and(|g| g != subscription.guild_id)
+            {
+                continue;
+            }
+            let track = event.data.as_ref().map(|d| d.get("track").unwrap_or(d));
+            let field = |k: &str| track.and_then(|t| t.get(k)).and_then(|v| v.as_str());
+            let title = match (field("title"), field("author")) {
+                (Some(t), Some(a)) => format!("{} by {}", t, a),
+                (Some(t), None) => t.to_string(),
+                _ => "a new track".to_string(),
+            };
+            let line = format!("{} Now playing: {}", "🎵".cyan(), title.bold());
+            if printer.print(line).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete(line: &str) -> Vec<String> {
+        let helper = ShellHelper::new(crate::cli_command(), "default".to_string());
+        let start = line.rfind(' ').map_or(0, |i| i + 1);
+        let words: Vec<&str> = line[..start].split_whitespace().collect();
+        let word = &line[start..];
+        let mut candidates = helper.candidates(&words, word);
+        candidates.retain(|c| c.starts_with(word));
+        candidates
+    }
+
+    #[test]
+    fn completes_from_the_cli_definition() {
+        let cases = [
+            ("pa", "pause"),
+            ("", "turip"),
+            ("filter ", "custom"),
+            ("queue --", "--limit"),
+            // global flags after a subcommand, and their values
+            ("skip --", "--dry-run"),
+            ("--dry-run c", "curl"),
+        ];
+        for (line, expected) in cases {
+            let candidates = complete(line);
+            assert!(candidates.iter().any(|c| c == expected), "{:?}: {:?}", line, candidates);
+        }
+        // a subcommand is only offered in first position
+        assert!(!complete("play lofi pa").contains(&"pause".to_string()));
+    }
+}
@@ -0,0 +1,285 @@
+//! Listening statistics ("Jorik Wrapped") computed from the local history.
+
+use crate::history::{HistoryEntry, HistoryEvent};
+use chrono::{DateTime, Local, Timelike};
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Count {
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HourCount {
+    pub hour: u32,
+    pub count: usize,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Stats {
+    pub plays: usize,
+    /// Only skips made from this machine; the server sends no skip events
+    pub skips: usize,
+    pub skip_rate: f64,
+    /// Time between a track starting and it finishing or being skipped
+    pub total_listening_ms: u64,
+    pub top_tracks: Vec<Count>,
+    pub top_artists: Vec<Count>,
+    pub top_requesters: Vec<Count>,
+    pub busiest_hours: Vec<HourCount>,
+}
+
+/// Build the report from history entries, in any order.
+///
+/// Plays are the tracks seen starting over the WS. When only CLI activity was
+/// recorded (no `started` entries), queued tracks stand in for plays.
+pub fn compute(entries: &[HistoryEntry], top: usize) -> Stats {
+    let started: Vec<&HistoryEntry> = entries
+        .iter()
+        .filter(|e| e.event == HistoryEvent::Started)
+        .collect();
+    let plays: Vec<&HistoryEntry> = if started.is_empty() {
+        entries
+            .iter()
+            .filter(|e| e.event == HistoryEvent::Queued)
+            .collect()
+    } else {
+        started
+    };
+    let skips = entries
+        .iter()
+        .filter(|e| e.event == HistoryEvent::Skipped)
+        .count();
+
+    let mut hours: HashMap<u32, usize> = HashMap::new();
+    for entry in &plays {
+        if let Some(t) = DateTime::from_timestamp(entry.at, 0) {
+            *hours.entry(t.with_timezone(&Local).hour()).or_default() += 1;
+        }
+    }
+    let mut busiest_hours: Vec<HourCount> = hours
+        .into_iter()
+        .map(|(hour, count)| HourCount { hour, count })
+        .collect();
+    busiest_hours.sort_by(|a, b| b.count.cmp(&a.count).then(a.hour.cmp(&b.hour)));
+    busiest_hours.truncate(top);
+
+    Stats {
+        plays: plays.len(),
+        skips,
+        skip_rate: if plays.is_empty() {
+            0.0
+        } else {
+            skips as f64 / plays.len() as f64
+        },
+        total_listening_ms: listened_ms(entries),
+        top_tracks: top_counts(plays.iter().map(|e| e.display_title()), top),
+        top_artists: top_counts(
+            plays
+                .iter()
+                .filter(|e| !e.author.is_empty())
+                .map(|e| e.author.clone()),
+            top,
+        ),
+        top_requesters: top_counts(plays.iter().filter_map(|e| e.requested_by.clone()), top),
+        busiest_hours,
+    }
+}
+
+/// Sum the time from each `started` entry to the `finished` or `skipped` entry
+/// for the same track, capped at the track's length. Tracks without both ends
+/// in `entries` (still playing, or started before the window) don't count.
+fn listened_ms(entries: &[HistoryEntry]) -> u64 {
+    let mut sorted: Vec<&HistoryEntry> = entries.iter().collect();
+    sorted.sort_by_key(|e| e.at);
+    let mut playing: HashMap<(Option<&str>, &str), i64> = HashMap::new();
+    let mut total = 0;
+    for entry in sorted {
+        let key = (entry.guild_id.as_deref(), entry.uri.as_str());
+        match entry.event {
+            HistoryEvent::Started => {
+                playing.insert(key, entry.at);
+            }
+            HistoryEvent::Finished | HistoryEvent::Skipped => {
+                if let Some(started) = playing.remove(&key) {
+                    let ms = (entry.at - started).max(0) as u64 * 1000;
+                    total += if entry.duration_ms > 0 { ms.min(entry.duration_ms) } else { ms };
+                }
+            }
+            HistoryEvent::Queued => {}
+        }
+    }
+    total
+}
+
+fn top_counts(items: impl Iterator<Item = String>, top: usize) -> Vec<Count> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for item in items {
+        *counts.entry(item).or_default() += 1;
+    }
+    let mut counts: Vec<Count> = counts
+        .into_iter()
+        .map(|(name, count)| Count { name, count })
+        .collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    counts.truncate(top);
+    counts
+}
+
+pub fn format_listening_time(ms: u64) -> String {
+    let minutes = ms / 60_000;
+    if minutes >= 60 {
+        format!("{}h {}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// The report as `(is_heading, text)` lines, shared by the CLI table and the
+/// TUI tab.
+pub fn lines(stats: &Stats) -> Vec<(bool, String)> {
+    let mut out = vec![
+        (false, format!("Plays:           {}", stats.plays)),
+        (
+            false,
+            format!(
+                "Skips:           {} ({:.0}% skip rate, skips from this machine only)",
+                stats.skips,
+                stats.skip_rate * 100.0
+            ),
+        ),
+        (
+            false,
+            format!(
+                "Listening time:  {}",
+                format_listening_time(stats.total_listening_ms)
+            ),
+        ),
+    ];
+    let mut section = |title: &str, rows: Vec<String>| {
+        out.push((false, String::new()));
+        out.push((true, title.to_string()));
+        if rows.is_empty() {
+            out.push((false, "  -".to_string()));
+        }
+        out.extend(rows.into_iter().map(|r| (false, r)));
+    };
+    let counts = |counts: &[Count]| -> Vec<String> {
+        counts
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("  {:>2}. {:<40} {:>4}", i + 1, c.name, c.count))
+            .collect()
+    };
+    section("Top tracks", counts(&stats.top_tracks));
+    section("Top artists", counts(&stats.top_artists));
+    section("Top requesters", counts(&stats.top_requesters));
+    section(
+        "Busiest hours",
+        stats
+            .busiest_hours
+            .iter()
+            .map(|h| format!("  {:02}:00-{:02}:59  {:>4}", h.hour, h.hour, h.count))
+            .collect(),
+    );
+    out
+}
+
+pub fn print_table(stats: &Stats) {
+    println!("{}", "Listening Stats".bold().underline());
+    for (heading, line) in lines(stats) {
+        if heading {
+            println!("{}", line.bold());
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(at: i64, event: HistoryEvent, title: &str, author: &str) -> HistoryEntry {
+        HistoryEntry {
+            at,
+            event,
+            title: title.to_string(),
+            author: author.to_string(),
+            uri: format!("uri:{}", title),
+            duration_ms: 180_000,
+            requested_by: Some("bob".to_string()),
+            guild_id: Some("42".to_string()),
+        }
+    }
+
+    #[test]
+    fn computes_stats() {
+        use HistoryEvent::*;
+        let cases = [
+            // finished at full length, skipped after 10s, still playing
+            (
+                vec![
+                    entry(0, Started, "a", "x"),
+                    entry(180, Finished, "a", "x"),
+                    entry(180, Started, "b", "y"),
+                    entry(190, Skipped, "b", "y"),
+                    entry(190, Started, "c", "y"),
+                ],
+                (3, 1, 190_000),
+            ),
+            // started before the window: its end alone doesn't count
+            (
+                vec![entry(10, Finished, "a", "x"), entry(10, Started, "b", "y")],
+                (1, 0, 0),
+            ),
+            // a track end arriving late is capped at the track's length
+            (
+                vec![entry(0, Started, "a", "x"), entry(500, Finished, "a", "x")],
+                (1, 0, 180_000),
+            ),
+            // CLI-only history: queued tracks count as plays, nothing as listened
+            (
+                vec![entry(0, Queued, "a", "x"), entry(5, Skipped, "a", "x")],
+                (1, 1, 0),
+            ),
+        ];
+        for (mut entries, (plays, skips, listened)) in cases {
+            // history::query hands entries over newest first
+            entries.reverse();
+            let stats = compute(&entries, 5);
+            assert_eq!(
+                (stats.plays, stats.skips, stats.total_listening_ms),
+                (plays, skips, listened),
+                "{:?}",
+                entries
+            );
+        }
+    }
+
+    #[test]
+    fn orders_top_counts() {
+        use HistoryEvent::Started;
+        let entries = [
+            entry(0, Started, "b", "y"),
+            entry(1, Started, "a", "x"),
+            entry(2, Started, "c", "y"),
+            entry(3, Started, "a", "x"),
+            entry(4, Started, "c", "z"),
+        ];
+        let stats = compute(&entries, 2);
+        let names = |counts: &[Count]| -> Vec<(String, usize)> {
+            counts.iter().map(|c| (c.name.clone(), c.count)).collect()
+        };
+        // ties broken by name, cut at `top`
+        assert_eq!(
+            names(&stats.top_tracks),
+            [("a by x".to_string(), 2), ("b by y".to_string(), 1)]
+        );
+        assert_eq!(names(&stats.top_artists), [("x".to_string(), 2), ("y".to_string(), 2)]);
+        assert_eq!(names(&stats.top_requesters), [("bob".to_string(), 5)]);
+    }
+}
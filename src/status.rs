@@ -0,0 +1,424 @@
+//! `jorik status`: the current track as a line for a status bar, in the
+//! format each bar reads natively, optionally kept up to date from the
+//! WebSocket (`--follow`) so the bar doesn't have to poll.
+//!
+//! ```text
+//! "custom/jorik": { "exec": "jorik status --format waybar --follow", "return-type": "json" }
+//! ```
+
+use crate::api::{self, PlaybackState, SimplePayload, fetch_audio};
+use crate::follow;
+use crate::search::format_duration;
+use crate::ws::{self, Update};
+use anyhow::{Context, Result, bail};
+use clap::{Args, ValueEnum};
+use reqwest::Client;
+use serde_json::{Value, json};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Text with icons
+    Plain,
+    /// JSON with text, tooltip, class and percentage (`"return-type": "json"`)
+    Waybar,
+    /// Text with click actions: left toggles pause, right skips
+    Polybar,
+    /// full_text, short_text and color lines (one line per update with --follow)
+    I3blocks,
+    /// Text with `#` escaped for `status-right`
+    Tmux,
+}
+
+#[derive(Args, Debug)]
+pub struct StatusArgs {
+    #[arg(long, value_enum, default_value = "plain")]
+    pub format: Format,
+    /// Keep running and print a new line whenever playback changes
+    #[arg(long)]
+    pub follow: bool,
+    /// Shorten the track to this many characters
+    #[arg(long)]
+    pub max_length: Option<usize>,
+    #[arg(long)]
+    pub guild_id: Option<String>,
+    #[arg(long)]
+    pub user_id: Option<String>,
+}
+
+const PLAYING: &str = "▶";
+const PAUSED: &str = "⏸";
+const LOOP_TRACK: &str = "🔂";
+const LOOP_QUEUE: &str = "🔁";
+const TWENTY_FOUR_SEVEN: &str = "🌙";
+
+/// Events after which the track itself may have changed.
+const REFETCH: &[&str] = &["initial_state", "track_start", "track_end", "queue_update", "player_update"];
+
+/// What the bar shows, from a `nowplaying` response and the ticks after it.
+/// `jorik mpris` publishes the same.
+#[derive(Clone, PartialEq)]
+pub struct Now {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub uri: Option<String>,
+    pub artwork_url: Option<String>,
+    pub elapsed_ms: u64,
+    pub duration_ms: u64,
+    pub paused: bool,
+    /// `track` or `queue` when looping
+    pub loop_mode: Option<String>,
+    pub twenty_four_seven: bool,
+}
+
+impl Now {
+    pub fn from_response(json: &Value) -> Self {
+        let np = json.get("now_playing").filter(|np| np.is_object());
+        let track = np.and_then(|np| np.get("track"));
+        let text = |k: &str| track.and_then(|t| t.get(k)).and_then(Value::as_str).map(str::to_string);
+        let number = |k: &str| np.and_then(|np| np.get(k)).and_then(Value::as_u64).unwrap_or(0);
+        Now {
+            title: text("title"),
+            author: text("author").filter(|a| !a.is_empty()),
+            uri: text("uri"),
+            artwork_url: text("artworkUrl").or_else(|| text("thumbnail")),
+            elapsed_ms: number("elapsedMs"),
+            duration_ms: number("durationMs"),
+            paused: np
+                .and_then(|np| np.get("paused"))
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            loop_mode: find(json, &["loop", "loopMode", "loop_mode"])
+                .and_then(Value::as_str)
+                .filter(|m| *m != "off")
+                .map(str::to_string),
+            twenty_four_seven: find(json, &["247", "twentyFourSeven", "twenty_four_seven"])
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+        }
+    }
+
+    pub fn tick(&mut self, playback: &PlaybackState) {
+        self.elapsed_ms = playback.elapsed_ms;
+        self.duration_ms = playback.duration_ms;
+        self.paused = playback.paused;
+    }
+
+    fn state(&self) -> &'static str {
+        match (&self.title, self.paused) {
+            (None, _) => "stopped",
+            (Some(_), true) => "paused",
+            (Some(_), false) => "playing",
+        }
+    }
+
+    /// `▶ Title - Author 🔁 🌙`, or nothing when idle so bars can hide.
+    fn text(&self, max_length: Option<usize>) -> String {
+        let Some(title) = &self.title else {
+            return String::new();
+        };
+        let track = match &self.author {
+            Some(author) => format!("{} - {}", title, author),
+            None => title.clone(),
+        };
+        let mut text = format!(
+            "{} {}",
+            if self.paused { PAUSED } else { PLAYING },
+            shorten(&track, max_length)
+        );
+        for icon in self.mode_icons() {
+            text.push(' ');
+            text.push_str(icon);
+        }
+        text
+    }
+
+    fn mode_icons(&self) -> Vec<&'static str> {
+        let mut icons = Vec::new();
+        match self.loop_mode.as_deref() {
+            Some("track") => icons.push(LOOP_TRACK),
+            Some(_) => icons.push(LOOP_QUEUE),
+            None => {}
+        }
+        if self.twenty_four_seven {
+            icons.push(TWENTY_FOUR_SEVEN);
+        }
+        icons
+    }
+
+    fn tooltip(&self) -> String {
+        let Some(title) = &self.title else {
+            return "Nothing playing".to_string();
+        };
+        let mut lines = vec![title.clone()];
+        lines.extend(self.author.clone());
+        if self.duration_ms > 0 {
+            lines.push(format!(
+                "{} / {}",
+                format_duration(self.elapsed_ms),
+                format_duration(self.duration_ms)
+            ));
+        }
+        if let Some(mode) = &self.loop_mode {
+            lines.push(format!("Loop: {}", mode));
+        }
+        if self.twenty_four_seven {
+            lines.push("24/7".to_string());
+        }
+        lines.join("\n")
+    }
+
+    fn percentage(&self) -> u64 {
+        (self.elapsed_ms * 100).checked_div(self.duration_ms).unwrap_or(0).min(100)
+    }
+
+    fn render(&self, format: Format, max_length: Option<usize>, follow: bool) -> String {
+        let text = self.text(max_length);
+        match format {
+            Format::Plain => text,
+            Format::Waybar => {
+                let mut class = vec![self.state().to_string()];
+                if let Some(mode) = &self.loop_mode {
+                    class.push(format!("loop-{}", mode));
+                }
+                if self.twenty_four_seven {
+                    class.push("247".to_string());
+                }
+                json!({
+                    "text": text,
+                    "alt": self.state(),
+                    "tooltip": self.tooltip(),
+                    "class": class,
+                    "percentage": self.percentage(),
+                })
+                .to_string()
+            }
+            Format::Polybar => {
+                if text.is_empty() {
+                    return text;
+                }
+                // `%{` would start a formatting tag
+                let text = text.replace("%{", "% {");
+                format!("%{{A1:jorik pause:}}%{{A3:jorik skip:}}{}%{{A}}%{{A}}", text)
+            }
+            Format::I3blocks => {
+                // With `interval=persist` every line is a new full_text
+                if follow {
+                    return text;
+                }
+                let short = self.title.as_deref().map(|t| shorten(t, Some(20))).unwrap_or_default();
+                let color = if self.paused { "#888888" } else { "" };
+                format!("{}\n{}\n{}", text, short, color)
+            }
+            Format::Tmux => text.replace('#', "##"),
+        }
+    }
+}
+
+/// Look for the first of `keys` at the top of a response or in the objects
+/// servers tend to nest player state in.
+fn find<'a>(json: &'a Value, keys: &[&str]) -> Option<&'a Value> {
+    [Some(json), json.get("now_playing"), json.get("queue"), json.get("player")]
+        .into_iter()
+        .flatten()
+        .find_map(|v| keys.iter().find_map(|k| v.get(*k)))
+}
+
+fn shorten(text: &str, max_length: Option<usize>) -> String {
+    match max_length {
+        Some(max) if text.chars().count() > max => {
+            let mut short: String = text.chars().take(max.saturating_sub(1)).collect();
+            short.push('…');
+            short
+        }
+        _ => text.to_string(),
+    }
+}
+
+pub async fn run(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    args: StatusArgs,
+) -> Result<()> {
+    if !args.follow {
+        let payload = SimplePayload {
+            action: "nowplaying",
+            guild_id: args.guild_id,
+            user_id: args.user_id,
+        };
+        let json = fetch_audio(client, base_url, token, &payload).await?;
+        if api::dry_run().is_none() {
+            println!("{}", Now::from_response(&json).render(args.format, args.max_length, false));
+        }
+        return Ok(());
+    }
+
+    let mut watcher = Watcher::start(client, base_url, token, args.guild_id, args.user_id).await?;
+    let mut last = watcher.now.render(args.format, args.max_length, true);
+    println!("{}", last);
+    while let Some(now) = watcher.next().await {
+        let line = now.render(args.format, args.max_length, true);
+        if line != last {
+            println!("{}", line);
+            last = line;
+        }
+    }
+    Ok(())
+}
+
+/// Keeps a [`Now`] current from the WebSocket: ticks move the clock, and the
+/// track is fetched again whenever it may have changed.
+pub struct Watcher {
+    client: Client,
+    base_url: String,
+    token: String,
+    payload: SimplePayload,
+    updates: UnboundedReceiver<Update>,
+    /// Last state_update without the clock, to tell ticks from changes
+    last_state: Option<Value>,
+    reconnecting: bool,
+    pub guild_id: String,
+    pub now: Now,
+}
+
+impl Watcher {
+    pub async fn start(
+        client: &Client,
+        base_url: &str,
+        token: Option<&str>,
+        guild_id: Option<String>,
+        user_id: Option<String>,
+    ) -> Result<Self> {
+        if api::dry_run().is_some() {
+            bail!("--follow cannot be combined with --dry-run");
+        }
+        let token = token.context("following playback needs a login; run `jorik auth login` first")?;
+        let mut payload = SimplePayload {
+            action: "nowplaying",
+            guild_id: guild_id.clone(),
+            user_id,
+        };
+        let json = fetch_audio(client, base_url, Some(token), &payload).await?;
+        let guild_id = guild_id
+            .or_else(|| ws::guild_from_response(&json))
+            .context("could not determine the guild to follow; pass --guild-id")?;
+        payload.guild_id = Some(guild_id.clone());
+        Ok(Watcher {
+            client: client.clone(),
+            base_url: base_url.to_string(),
+            token: token.to_string(),
+            payload,
+            updates: ws::subscribe(base_url.to_string(), token.to_string(), guild_id.clone()),
+            last_state: None,
+            reconnecting: false,
+            guild_id,
+            now: Now::from_response(&json),
+        })
+    }
+
+    /// Wait for the next update that may have changed [`Watcher::now`].
+    /// `None` once the subscription ends.
+    pub async fn next(&mut self) -> Option<&Now> {
+        loop {
+            let refetch = match self.updates.recv().await? {
+                // Anything may have happened while we were away
+                Update::Connected => std::mem::take(&mut self.reconnecting),
+                Update::Disconnected(_) => {
+                    self.reconnecting = true;
+                    continue;
+                }
+                Update::Event { event, .. } => {
+                    if event.guild_id.as_deref().is_some_and(|g| g != self.guild_id) {
+                        continue;
+                    }
+                    if let Some(playback) = ws::playback(&event) {
+                        self.now.tick(&playback);
+                    }
+                    match event.event_type.as_str() {
+                        "state_update" => {
+                            let state = event.data.as_ref().map(follow::stable);
+                            let changed = state.is_some() && state != self.last_state;
+                            self.last_state = state;
+                            changed
+                        }
+                        t => REFETCH.contains(&t),
+                    }
+                }
+            };
+            if refetch {
+                self.refresh().await;
+            }
+            return Some(&self.now);
+        }
+    }
+
+    /// Fetch the track again, keeping the old one if that fails.
+    pub async fn refresh(&mut self) {
+        match fetch_audio(&self.client, &self.base_url, Some(&self.token), &self.payload).await {
+            Ok(json) => self.now = Now::from_response(&json),
+            Err(e) => eprintln!("{:#}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now(paused: bool) -> Now {
+        Now::from_response(&json!({
+            "now_playing": {
+                "track": {"title": "Song #1", "author": "Artist"},
+                "elapsedMs": 30000,
+                "durationMs": 120000,
+                "paused": paused,
+            },
+            "loop": "queue",
+        }))
+    }
+
+    #[test]
+    fn renders_each_format() {
+        let cases = [
+            (Format::Plain, false, "▶ Song #1 - Artist 🔁"),
+            (Format::Tmux, false, "▶ Song ##1 - Artist 🔁"),
+            (
+                Format::Polybar,
+                false,
+                "%{A1:jorik pause:}%{A3:jorik skip:}▶ Song #1 - Artist 🔁%{A}%{A}",
+            ),
+            (Format::I3blocks, false, "▶ Song #1 - Artist 🔁\nSong #1\n"),
+            (Format::I3blocks, true, "⏸ Song #1 - Artist 🔁\nSong #1\n#888888"),
+        ];
+        for (format, paused, expected) in cases {
+            assert_eq!(now(paused).render(format, None, false), expected);
+        }
+    }
+
+    #[test]
+    fn renders_waybar_json() {
+        let rendered = now(true).render(Format::Waybar, None, false);
+        let rendered: Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(
+            rendered,
+            json!({
+                "text": "⏸ Song #1 - Artist 🔁",
+                "alt": "paused",
+                "tooltip": "Song #1\nArtist\n0:30 / 2:00\nLoop: queue",
+                "class": ["paused", "loop-queue"],
+                "percentage": 25,
+            })
+        );
+    }
+
+    #[test]
+    fn shortens_and_hides_when_idle() {
+        assert_eq!(now(false).render(Format::Plain, Some(8), false), "▶ Song #1… 🔁");
+        let idle = Now::from_response(&json!({"now_playing": null}));
+        for format in [Format::Plain, Format::Polybar, Format::Tmux] {
+            assert_eq!(idle.render(format, None, true), "");
+        }
+        assert!(idle.render(Format::Waybar, None, true).contains("\"class\":[\"stopped\"]"));
+    }
+}
@@ -1,4 +1,12 @@
-use crate::api::{self, AudioFilters, EqualizerBand, FilterPayload, KaraokeOptions, LoopPayload, LowPassOptions, LyricsPayload, PlayPayload, QueuePayload, RotationOptions, SimplePayload, TimescaleOptions, TremoloOptions, TwentyFourSevenPayload, VibratoOptions, WsEvent, WsSubscribe, PlaybackState};
+use crate::api::{self, AudioFilters, FilterPayload, LoopPayload, LyricsPayload, PlayPayload, QueuePayload, SearchPayload, SimplePayload, TwentyFourSevenPayload, WsEvent, PlaybackState};
+use crate::aliases::{self, Alias};
+use crate::favorites::{self, Favorite};
+use crate::filters::{self, Preset};
+use crate::history;
+use crate::stats;
+use crate::undo;
+use crate::ws;
+use crate::search::{self, SearchTrack};
 use crate::ascii::ASCII_LOGO;
 use anyhow::Result;
 use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
@@ -12,14 +20,14 @@ use ratatui::{
 use ratatui::style::Stylize;
 use reqwest::Client;
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::{sync::Arc, time::{Duration, Instant}};
 use tokio::sync::Mutex;
 use tokio::time::{interval, timeout};
 use tokio::net::TcpListener;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use futures_util::{StreamExt, SinkExt};
-use tokio_tungstenite::{connect_async, tungstenite::{protocol::Message, client::IntoClientRequest, http::HeaderValue}};
-use url::Url;
+use tokio_tungstenite::tungstenite::protocol::Message;
 
 
 
@@ -102,13 +110,27 @@ enum View {
     Menu,
     Lyrics,
     FilterMenu,
+    Favorites,
+    SearchResults,
     AuthMenu,
     AuthResult,
     LoginRequired,
     Settings,
     Debug,
+    Stats,
     AppInfo,
     UpdateFound,
+    Confirm,
+}
+
+/// How long a toast stays on screen (and `u` can undo).
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+
+struct Toast {
+    message: String,
+    shown_at: Instant,
+    /// Whether `u` restores the snapshot taken for this action
+    undoable: bool,
 }
 
 #[derive(PartialEq, Clone, Copy)]
@@ -118,6 +140,7 @@ enum SettingsField {
     Theme,
     VizStyle,
     Layout,
+    StatsTab,
 }
 
 struct App {
@@ -128,10 +151,14 @@ struct App {
     user_id: Option<String>,
     
     queue: Vec<String>,
+    /// Upcoming tracks on the server; `queue` may only hold the first page
+    queue_total: usize,
     current_track: Option<String>,
+    current_track_data: Option<Value>,
     error_message: Option<String>,
     fatal_error: Option<String>,
     loop_mode: String, // "off", "track", "queue"
+    active_filters: AudioFilters,
     is_loading: bool,
     
     input: String,
@@ -139,10 +166,22 @@ struct App {
     view: View,
     
     menu_state: ListState,
-    menu_items: Vec<&'static str>,
+    menu_items: Vec<String>,
+    confirm_threshold: usize,
+    /// `stop`/`clear` waiting for a yes in the confirm dialog
+    pending_confirm: Option<&'static str>,
+    toast: Option<Toast>,
+    aliases: BTreeMap<String, Alias>,
     
     filter_state: ListState,
-    filter_items: Vec<&'static str>,
+    filter_items: Vec<Preset>,
+
+    search_state: ListState,
+    search_results: Vec<SearchTrack>,
+
+    profile: String,
+    favorites_state: ListState,
+    favorite_items: Vec<Favorite>,
     
     auth_menu_state: ListState,
     auth_menu_items: Vec<&'static str>,
@@ -164,6 +203,8 @@ struct App {
     theme: String,
     viz_style: String,
     layout: String,
+    show_stats_tab: bool,
+    stats_lines: Vec<(bool, String)>,
     settings_field: SettingsField,
     is_settings_editing: bool,
     needs_reconnect: bool,
@@ -186,6 +227,7 @@ impl App {
         token: Option<String>,
         guild_id: Option<String>,
         user_id: Option<String>,
+        profile: String,
     ) -> Self {
         let mut menu_state = ListState::default();
         menu_state.select(Some(0));
@@ -198,6 +240,15 @@ impl App {
         
         let view = if token.is_some() { View::Main } else { View::LoginRequired };
 
+        let aliases = aliases::all(&settings);
+        let mut menu_items: Vec<String> = [
+            " [+] Skip ", " [||] Pause/Resume ", " [X] Stop ", " [/] Shuffle ",
+            " [C] Clear Queue ", " [T] Loop Track ", " [Q] Loop Queue ", " [.] Loop Off ",
+            " [24/7] Mode Toggle ", " [F] Filters... ", " [*] Favorites... ", " [L] Lyrics ",
+        ].iter().map(|s| s.to_string()).collect();
+        menu_items.extend(aliases.keys().map(|name| format!(" [>] {} ", name)));
+        menu_items.extend([" [A] Auth ", " [S] Settings ", " [!] Exit TUI "].iter().map(|s| s.to_string()));
+
         Self {
             client,
             base_url: settings.base_url.clone(),
@@ -205,26 +256,30 @@ impl App {
             guild_id,
             user_id,
             queue: Vec::new(),
+            queue_total: 0,
             current_track: None,
+            current_track_data: None,
             error_message: None,
             fatal_error: None,
             loop_mode: "off".to_string(),
+            active_filters: AudioFilters::default(),
             is_loading: false,
             input: String::new(),
             input_mode: InputMode::Normal,
             view,
             menu_state,
-            menu_items: vec![
-                " [+] Skip ", " [||] Pause/Resume ", " [X] Stop ", " [/] Shuffle ", 
-                " [C] Clear Queue ", " [T] Loop Track ", " [Q] Loop Queue ", " [.] Loop Off ",
-                " [24/7] Mode Toggle ", " [F] Filters... ", " [L] Lyrics ", " [P] Play Turip ",
-                " [A] Auth ", " [S] Settings ", " [!] Exit TUI "
-            ],
+            menu_items,
+            aliases,
+            confirm_threshold: settings.confirm_threshold,
+            pending_confirm: None,
+            toast: None,
             filter_state,
-            filter_items: vec![
-                "Clear", "Bassboost", "Nightcore", "Vaporwave", 
-                "8D", "Soft", "Tremolo", "Vibrato", "Karaoke"
-            ],
+            filter_items: filters::load_presets().0,
+            search_state: ListState::default(),
+            search_results: Vec::new(),
+            favorites_state: ListState::default(),
+            favorite_items: favorites::load(&profile),
+            profile,
             auth_menu_state,
             auth_menu_items: vec!["Login", "Signout", "Info"],
             lyrics_text: None,
@@ -240,6 +295,8 @@ impl App {
             theme: settings.theme,
             viz_style: settings.visualizer_style,
             layout: settings.layout,
+            show_stats_tab: settings.show_stats_tab,
+            stats_lines: Vec::new(),
             settings_field: SettingsField::Host,
             is_settings_editing: false,
             needs_reconnect: false,
@@ -298,6 +355,29 @@ impl App {
         }
     }
 
+    fn star_current_track(&mut self) {
+        let Some(favorite) = self.current_track_data.as_ref().and_then(Favorite::from_track) else {
+            self.log("Nothing playing to star");
+            return;
+        };
+        match favorites::add(&self.profile, favorite) {
+            Ok(stored) => self.log(format!("Starred '{}'", stored.name)),
+            Err(e) => self.log(format!("Failed to star track: {}", e)),
+        }
+        self.favorite_items = favorites::load(&self.profile);
+    }
+
+    fn is_current_favorite(&self) -> bool {
+        let uri = self.current_track_data.as_ref().and_then(|t| t.get("uri")).and_then(|v| v.as_str());
+        uri.is_some_and(|uri| self.favorite_items.iter().any(|f| f.query == uri))
+    }
+
+    fn refresh_stats(&mut self) {
+        let week = Duration::from_secs(7 * 24 * 60 * 60);
+        let entries = history::query(Some(week), None, self.guild_id.as_deref());
+        self.stats_lines = stats::lines(&stats::compute(&entries, 5));
+    }
+
     fn parse_queue_response(&mut self, json: &Value) {
         // Handle nested queue object if present
         let target = if let Some(queue) = json.get("queue") {
@@ -323,13 +403,19 @@ impl App {
             let title = current.get("title").and_then(|v| v.as_str()).unwrap_or("Unknown");
             let author = current.get("author").and_then(|v| v.as_str()).unwrap_or("");
             self.current_track = Some(format!("{} - {}", title, author));
+            self.current_track_data = target.get("current").cloned();
         } else {
             // Only clear current_track if we are sure we are looking at a queue object
             if target.get("current").is_some() || target.get("upcoming").is_some() {
                 self.current_track = None;
+                self.current_track_data = None;
             }
         }
 
+        if let Some(active) = filters::from_response(target).or_else(|| filters::from_response(json)) {
+            self.active_filters = active;
+        }
+
         if let Some(upcoming) = target.get("upcoming").and_then(|v| v.as_array()) {
             self.queue.clear();
             for item in upcoming {
@@ -337,9 +423,32 @@ impl App {
                 let author = item.get("author").and_then(|v| v.as_str()).unwrap_or("");
                 self.queue.push(format!("{} - {}", title, author));
             }
+            self.queue_total = target
+                .get("total_upcoming")
+                .and_then(|v| v.as_u64())
+                .map_or(self.queue.len(), |t| t as usize);
+        }
+    }
+
+    /// Send `stop`/`clear`, asking first when the queue is longer than the
+    /// confirm threshold.
+    fn request_wipe(&mut self, action: &'static str, app_arc: Arc<Mutex<App>>) {
+        if self.queue_total > self.confirm_threshold {
+            self.pending_confirm = Some(action);
+            self.view = View::Confirm;
+        } else {
+            tokio::spawn(async_undoable_command(app_arc, action));
         }
     }
 
+    fn show_toast(&mut self, message: impl Into<String>, undoable: bool) {
+        self.toast = Some(Toast { message: message.into(), shown_at: Instant::now(), undoable });
+    }
+
+    fn active_toast(&self) -> Option<&Toast> {
+        self.toast.as_ref().filter(|t| t.shown_at.elapsed() < TOAST_DURATION)
+    }
+
     fn update_realtime(&mut self) {
         if self.current_track.is_some() && !self.paused {
             let now = Instant::now();
@@ -447,6 +556,53 @@ async fn async_fetch_queue(app_arc: Arc<Mutex<App>>) {
     }
 }
 
+/// Run a macro alias through the CLI itself (`jorik run <name>`), so its steps
+/// behave exactly as on the command line. Output goes to the debug log.
+/// Picking the macro is the confirmation: there is no terminal for `stop` or
+/// `clear` to ask on, so they get `--yes`.
+async fn async_run_macro(app_arc: Arc<Mutex<App>>, name: String) {
+    let (base_url, token, profile) = {
+        let mut app = app_arc.lock().await;
+        app.is_loading = true;
+        app.log(format!("Running macro {}", name));
+        (app.base_url.clone(), app.token.clone(), app.profile.clone())
+    };
+
+    let macro_name = name.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let exe = std::env::current_exe()?;
+        let mut cmd = std::process::Command::new(exe);
+        cmd.args(["--base-url", &base_url, "--profile", &profile, "--yes", "run", &macro_name])
+            .env("NO_COLOR", "1")
+            .stdin(std::process::Stdio::null());
+        if let Some(token) = token {
+            cmd.env("JORIK_TOKEN", token);
+        }
+        if !crate::normalize::enabled() {
+            cmd.arg("--no-normalize");
+        }
+        cmd.output()
+    })
+    .await;
+
+    let mut app = app_arc.lock().await;
+    app.is_loading = false;
+    match result {
+        Ok(Ok(output)) => {
+            let text = String::from_utf8_lossy(&output.stdout).to_string()
+                + &String::from_utf8_lossy(&output.stderr);
+            for line in text.lines().filter(|l| !l.trim().is_empty()) {
+                app.log(line.to_string());
+            }
+            if !output.status.success() {
+                app.error_message = Some(format!("Macro {} failed (see debug log)", name));
+            }
+        }
+        Ok(Err(e)) => app.error_message = Some(format!("Failed to run macro {}: {}", name, e)),
+        Err(e) => app.error_message = Some(format!("Failed to run macro {}: {}", name, e)),
+    }
+}
+
 async fn async_play_track(app_arc: Arc<Mutex<App>>, query: String) {
     let (ws_sender, ws_connected, client, url, token, payload) = {
         let mut app = app_arc.lock().await;
@@ -455,10 +611,14 @@ async fn async_play_track(app_arc: Arc<Mutex<App>>, query: String) {
             action: "play",
             guild_id: app.guild_id.clone(),
             channel_id: None,
-            query: api::clean_query(&query),
+            query: search::resolve_query(
+                &api::clean_query(&query),
+                api::load_profile_settings(&app.profile).default_source,
+            ),
             user_id: app.user_id.clone(),
             requested_by: None,
             avatar_url: None,
+            position: None,
         };
         let url = api::build_url(&app.base_url, "/webhook/audio");
         (app.ws_sender.clone(), app.ws_connected, app.client.clone(), url, app.token.clone(), payload)
@@ -491,11 +651,65 @@ async fn async_play_track(app_arc: Arc<Mutex<App>>, query: String) {
         req = req.bearer_auth(bearer);
     }
 
-    let _ = req.send().await;
+    if let Ok(resp) = req.send().await
+        && resp.status().is_success()
+        && let Ok(json) = resp.json::<Value>().await
+    {
+        history::record_response(&json);
+    }
     tokio::time::sleep(Duration::from_millis(500)).await;
     async_fetch_queue(app_arc).await;
 }
 
+async fn async_search(app_arc: Arc<Mutex<App>>, query: String) {
+    let (client, url, token, payload) = {
+        let mut app = app_arc.lock().await;
+        app.is_loading = true;
+        let payload = SearchPayload {
+            action: "search",
+            guild_id: app.guild_id.clone(),
+            user_id: app.user_id.clone(),
+            query: search::search_query(
+                &query,
+                api::load_profile_settings(&app.profile)
+                    .default_source
+                    .unwrap_or(search::SearchSource::Youtube),
+            ),
+            limit: 10,
+        };
+        let url = api::build_url(&app.base_url, "/webhook/audio");
+        (app.client.clone(), url, app.token.clone(), payload)
+    };
+
+    let mut req = client.post(&url).json(&payload);
+    if let Some(bearer) = &token {
+        req = req.bearer_auth(bearer);
+    }
+
+    let result = req.send().await;
+
+    let mut app = app_arc.lock().await;
+    app.is_loading = false;
+    match result {
+        Ok(resp) => match resp.json::<Value>().await {
+            Ok(json) => {
+                let results = search::parse_tracks(&json);
+                app.log(format!("Search '{}' returned {} results", query, results.len()));
+                app.search_results = results;
+                if app.search_results.is_empty() {
+                    app.error_message = Some(format!("No results for '{}'", query));
+                } else {
+                    app.error_message = None;
+                    app.search_state.select(Some(0));
+                    app.view = View::SearchResults;
+                }
+            }
+            Err(e) => app.error_message = Some(format!("Search failed: {}", e)),
+        },
+        Err(e) => app.error_message = Some(format!("Search failed: {}", e)),
+    }
+}
+
 async fn async_fetch_lyrics(app_arc: Arc<Mutex<App>>) {
     let (ws_sender, ws_connected, client, url, token, payload) = {
         let mut app = app_arc.lock().await;
@@ -602,6 +816,73 @@ async fn async_simple_command<T: serde::Serialize + Send + Sync + 'static>(app_a
     async_fetch_queue(app_arc).await;
 }
 
+/// Snapshot the queue, then send `action`, offering an undo toast once it
+/// went through. Sent over REST rather than the WebSocket so the outcome is
+/// known before the snapshot is kept.
+async fn async_undoable_command(app_arc: Arc<Mutex<App>>, action: &'static str) {
+    let (client, base_url, token, guild_id, user_id) = {
+        let mut app = app_arc.lock().await;
+        app.is_loading = true;
+        (app.client.clone(), app.base_url.clone(), app.token.clone(), app.guild_id.clone(), app.user_id.clone())
+    };
+    let snapshot = undo::take(&client, &base_url, token.as_deref(), action, guild_id.clone(), user_id.clone()).await;
+    if let Err(e) = &snapshot {
+        app_arc.lock().await.log(format!("Queue snapshot failed, undo unavailable: {}", e));
+    }
+    let payload = SimplePayload { action, guild_id, user_id };
+    let url = api::build_url(&base_url, "/webhook/audio");
+    let result = match api::send_audio(&client, &base_url, token.as_deref(), &payload).await {
+        Ok(resp) => api::read_response(resp, &url).await,
+        Err(e) => Err(e),
+    };
+    {
+        let mut app = app_arc.lock().await;
+        app.is_loading = false;
+        match result {
+            Ok(_) => {
+                if let Ok(Some(snapshot)) = snapshot {
+                    match snapshot.save() {
+                        Ok(()) => {
+                            let label = match action {
+                                "stop" => "Stopped",
+                                "clear" => "Queue cleared",
+                                "skip" => "Skipped",
+                                _ => "Shuffled",
+                            };
+                            app.show_toast(format!("{} · u: undo", label), true);
+                        }
+                        Err(e) => app.log(format!("Saving undo snapshot failed: {}", e)),
+                    }
+                }
+            }
+            Err(e) => app.error_message = Some(format!("Error: {:#}", e)),
+        }
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    async_fetch_queue(app_arc).await;
+}
+
+async fn async_undo(app_arc: Arc<Mutex<App>>) {
+    let (client, base_url, token, user_id) = {
+        let mut app = app_arc.lock().await;
+        app.is_loading = true;
+        (app.client.clone(), app.base_url.clone(), app.token.clone(), app.user_id.clone())
+    };
+    let result = undo::restore(&client, &base_url, token.as_deref(), None, user_id).await;
+    {
+        let mut app = app_arc.lock().await;
+        app.is_loading = false;
+        match result {
+            Ok(summary) => {
+                app.log(summary.clone());
+                app.show_toast(summary, false);
+            }
+            Err(e) => app.error_message = Some(format!("Undo failed: {}", e)),
+        }
+    }
+    async_fetch_queue(app_arc).await;
+}
+
 fn escape_html(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
@@ -907,65 +1188,24 @@ async fn spawn_websocket(app_arc: Arc<Mutex<App>>, mut ws_rx: tokio::sync::mpsc:
         let token = token.unwrap();
         let guild_id = guild_id.unwrap();
 
-        let ws_url = match Url::parse(&base_url) {
-            Ok(u) => {
-                let scheme = if u.scheme() == "https" { "wss" } else { "ws" };
-                let mut u = u;
-                u.set_scheme(scheme).ok();
-                u.set_path("/ws");
-                u.query_pairs_mut().append_pair("token", &token);
-                u
-            }
-            Err(e) => {
-                let mut app = app_arc.lock().await;
-                app.log(format!("WS URL Parse Error: {}", e));
-                tokio::time::sleep(Duration::from_secs(5)).await;
-                continue;
-            }
-        };
-
         {
             let mut app = app_arc.lock().await;
-            app.log(format!("WS Connecting to {}", ws_url));
+            match ws::url(&base_url, &token) {
+                Ok(ws_url) => app.log(format!("WS Connecting to {}", ws_url)),
+                Err(e) => app.log(format!("WS URL Parse Error: {}", e)),
+            }
             app.ws_connected = false;
             app.ws_connecting = true;
         }
 
-        let request = match ws_url.as_str().into_client_request() {
-            Ok(mut req) => {
-                let headers = req.headers_mut();
-                headers.insert("User-Agent", HeaderValue::from_static("jorik-cli"));
-                headers.insert("Origin", HeaderValue::from_str(&base_url).unwrap_or_else(|_| HeaderValue::from_static("jorik-cli")));
-                if let Some(host) = ws_url.host_str() {
-                    headers.insert("Host", HeaderValue::from_str(host).unwrap_or_else(|_| HeaderValue::from_static("localhost")));
-                }
-                headers.insert("Authorization", HeaderValue::from_str(&format!("Bearer {}", token)).unwrap_or_else(|_| HeaderValue::from_static("")));
-                req
-            }
-            Err(e) => {
-                let mut app = app_arc.lock().await;
-                app.log(format!("WS Request Error: {}", e));
-                tokio::time::sleep(Duration::from_secs(5)).await;
-                continue;
-            }
-        };
-
-        match connect_async(request).await {
-            Ok((mut ws_stream, _)) => {
+        match ws::connect(&base_url, &token, &guild_id).await {
+            Ok(mut ws_stream) => {
                 {
                     let mut app = app_arc.lock().await;
                     app.log("WS Connected");
                     app.ws_connected = true;
                     app.ws_connecting = false;
                 }
-                
-                let sub = WsSubscribe {
-                    event_type: "subscribe",
-                    guild_id: guild_id.clone(),
-                };
-                if let Ok(json) = serde_json::to_string(&sub) {
-                    let _ = ws_stream.send(Message::Text(json.into())).await;
-                }
 
                 loop {
                     tokio::select! {
@@ -1032,6 +1272,7 @@ async fn spawn_websocket(app_arc: Arc<Mutex<App>>, mut ws_rx: tokio::sync::mpsc:
                                             "track_start" | "track_end" | "player_update" => {
                                                 if event.guild_id.as_deref() == app.guild_id.as_deref() {
                                                     app.log(format!("WS Event: {}, refreshing queue", event.event_type));
+                                                    history::record_event(&event.event_type, event.data.as_ref(), event.guild_id.as_deref());
                                                     // Trigger a full REST refresh to get the latest queue state
                                                     tokio::spawn(async_fetch_queue(app_arc.clone()));
                                                 }
@@ -1093,7 +1334,7 @@ async fn spawn_websocket(app_arc: Arc<Mutex<App>>, mut ws_rx: tokio::sync::mpsc:
             app.ws_connected = false;
             app.ws_connecting = false;
         }
-        tokio::time::sleep(Duration::from_secs(5)).await;
+        tokio::time::sleep(ws::RECONNECT_DELAY).await;
     }
 }
 
@@ -1102,6 +1343,7 @@ pub async fn run(
     token: Option<String>,
     guild_id: Option<String>,
     user_id: Option<String>,
+    profile: String,
 ) -> Result<Option<(String, Vec<api::GiteaAsset>)>> {
     let client = Client::builder()
         .user_agent("jorik-cli-tui")
@@ -1110,7 +1352,7 @@ pub async fn run(
 
     let (ws_tx, ws_rx) = tokio::sync::mpsc::unbounded_channel::<Message>();
 
-    let mut app_struct = App::new(client.clone(), settings, token, guild_id, user_id);
+    let mut app_struct = App::new(client.clone(), settings, token, guild_id, user_id, profile);
     app_struct.ws_sender = Some(ws_tx);
     
     let app = Arc::new(Mutex::new(app_struct));
@@ -1178,7 +1420,7 @@ async fn run_loop(terminal: &mut DefaultTerminal, app_arc: Arc<Mutex<App>>) -> R
                         continue;
                     }
 
-                    // Global Tab Switching (1-4)
+                    // Global Tab Switching (1-5)
                     match key.code {
                         KeyCode::Char('1') => { app.view = View::Main; continue; }
                         KeyCode::Char('2') => { 
@@ -1194,6 +1436,11 @@ async fn run_loop(terminal: &mut DefaultTerminal, app_arc: Arc<Mutex<App>>) -> R
                             continue; 
                         }
                         KeyCode::Char('4') => { app.view = View::Debug; continue; }
+                        KeyCode::Char('5') if app.show_stats_tab => {
+                            app.refresh_stats();
+                            app.view = View::Stats;
+                            continue;
+                        }
                         _ => {}
                     }
 
@@ -1209,12 +1456,22 @@ async fn run_loop(terminal: &mut DefaultTerminal, app_arc: Arc<Mutex<App>>) -> R
                                 return Ok(Some(update));
                             }
                         }
+                        View::Confirm => handle_confirm_keys(&mut app, key, app_arc.clone()),
                         View::Main => handle_player_keys(&mut *app, key, app_arc.clone()),
                         View::Lyrics => handle_lyrics_keys(&mut *app, key),
                         View::Settings => handle_settings_keys(&mut *app, key, app_arc.clone()),
                         View::Debug => handle_debug_keys(&mut *app, key),
+                        View::Stats => {
+                            match key.code {
+                                KeyCode::Esc | KeyCode::Backspace => app.view = View::Main,
+                                KeyCode::Char('r') | KeyCode::Char('к') => app.refresh_stats(),
+                                _ => {}
+                            }
+                        }
                         View::Menu => { if handle_menu_keys(&mut *app, key, app_arc.clone())? { return Ok(None); } },
                         View::FilterMenu => handle_filter_menu_keys(&mut *app, key, app_arc.clone()),
+                        View::SearchResults => handle_search_results_keys(&mut app, key, app_arc.clone()),
+                        View::Favorites => handle_favorites_keys(&mut app, key, app_arc.clone()),
                         View::AuthMenu => handle_auth_menu_keys(&mut *app, key, app_arc.clone()),
                         View::AuthResult => {
                             if matches!(key.code, KeyCode::Esc | KeyCode::Enter | KeyCode::Backspace) {
@@ -1249,7 +1506,12 @@ fn handle_editing_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mutex<A
             let query = app.input.clone();
             app.input.clear();
             app.input_mode = InputMode::Normal;
-            tokio::spawn(async_play_track(app_arc, query));
+            // Links are enqueued directly, free text opens the search results
+            if query.contains("://") {
+                tokio::spawn(async_play_track(app_arc, query));
+            } else if !query.trim().is_empty() {
+                tokio::spawn(async_search(app_arc, query));
+            }
         }
         KeyCode::Esc => {
             app.input_mode = InputMode::Normal;
@@ -1274,6 +1536,22 @@ fn handle_update_keys(app: &mut App, key: event::KeyEvent) -> Option<(String, Ve
     }
 }
 
+fn handle_confirm_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mutex<App>>) {
+    match key.code {
+        KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Char('н') | KeyCode::Char('Н') => {
+            if let Some(action) = app.pending_confirm.take() {
+                tokio::spawn(async_undoable_command(app_arc, action));
+            }
+            app.view = View::Main;
+        }
+        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Char('т') | KeyCode::Char('Т') | KeyCode::Esc => {
+            app.pending_confirm = None;
+            app.view = View::Main;
+        }
+        _ => {}
+    }
+}
+
 fn handle_player_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mutex<App>>) {
     match key.code {
         KeyCode::Char('r') | KeyCode::Char('к') => {
@@ -1292,20 +1570,21 @@ fn handle_player_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mutex<Ap
             tokio::spawn(async_simple_command(app_arc, "/webhook/audio".to_string(), LoopPayload { action: "loop", guild_id: app.guild_id.clone(), user_id: app.user_id.clone(), loop_mode: new_mode.to_string() }));
         }
         KeyCode::Char('s') | KeyCode::Char('ы') | KeyCode::Char('і') => {
-            tokio::spawn(async_simple_command(app_arc, "/webhook/audio".to_string(), SimplePayload { action: "skip", guild_id: app.guild_id.clone(), user_id: app.user_id.clone() }));
+            tokio::spawn(async_undoable_command(app_arc, "skip"));
         }
         KeyCode::Char('p') | KeyCode::Char('з') => {
             tokio::spawn(async_simple_command(app_arc, "/webhook/audio".to_string(), SimplePayload { action: "pause", guild_id: app.guild_id.clone(), user_id: app.user_id.clone() }));
         }
-        KeyCode::Char('w') | KeyCode::Char('ц') => {
-            tokio::spawn(async_simple_command(app_arc, "/webhook/audio".to_string(), SimplePayload { action: "stop", guild_id: app.guild_id.clone(), user_id: app.user_id.clone() }));
-        }
-        KeyCode::Char('c') | KeyCode::Char('с') => {
-            tokio::spawn(async_simple_command(app_arc, "/webhook/audio".to_string(), SimplePayload { action: "clear", guild_id: app.guild_id.clone(), user_id: app.user_id.clone() }));
-        }
+        KeyCode::Char('w') | KeyCode::Char('ц') => app.request_wipe("stop", app_arc),
+        KeyCode::Char('c') | KeyCode::Char('с') => app.request_wipe("clear", app_arc),
         KeyCode::Char('i') | KeyCode::Char('ш') => {
             app.view = View::AppInfo;
         }
+        KeyCode::Char('*') => app.star_current_track(),
+        KeyCode::Char('u') | KeyCode::Char('г') if app.active_toast().is_some_and(|t| t.undoable) => {
+            app.toast = None;
+            tokio::spawn(async_undo(app_arc));
+        }
         KeyCode::Char('d') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
             app.view = View::Debug;
         }
@@ -1390,16 +1669,18 @@ fn handle_settings_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mutex<
                 SettingsField::Offset => SettingsField::Theme,
                 SettingsField::Theme => SettingsField::VizStyle,
                 SettingsField::VizStyle => SettingsField::Layout,
-                SettingsField::Layout => SettingsField::Host,
+                SettingsField::Layout => SettingsField::StatsTab,
+                SettingsField::StatsTab => SettingsField::Host,
             };
         }
         KeyCode::Up => {
             app.settings_field = match app.settings_field {
-                SettingsField::Host => SettingsField::Layout,
+                SettingsField::Host => SettingsField::StatsTab,
                 SettingsField::Offset => SettingsField::Host,
                 SettingsField::Theme => SettingsField::Offset,
                 SettingsField::VizStyle => SettingsField::Theme,
                 SettingsField::Layout => SettingsField::VizStyle,
+                SettingsField::StatsTab => SettingsField::Layout,
             };
         }
         KeyCode::Right | KeyCode::Char('l') | KeyCode::Char('д') => {
@@ -1436,6 +1717,10 @@ fn handle_settings_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mutex<
                     };
                     save_app_settings(app);
                 }
+                SettingsField::StatsTab => {
+                    app.show_stats_tab = !app.show_stats_tab;
+                    save_app_settings(app);
+                }
                 _ => {}
             }
         }
@@ -1475,6 +1760,10 @@ fn handle_settings_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mutex<
                     };
                     save_app_settings(app);
                 }
+                SettingsField::StatsTab => {
+                    app.show_stats_tab = !app.show_stats_tab;
+                    save_app_settings(app);
+                }
                 _ => {}
             }
         }
@@ -1482,15 +1771,25 @@ fn handle_settings_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mutex<
     }
 }
 
-fn save_app_settings(app: &App) {
-    let settings = api::Settings { 
-        base_url: app.settings_input.clone(),
-        visualizer_offset: app.offset_input.parse().unwrap_or(app.visualizer_offset),
-        theme: app.theme.clone(),
-        visualizer_style: app.viz_style.clone(),
-        layout: app.layout.clone(),
+fn save_app_settings(app: &mut App) {
+    // Start from the saved file so settings the TUI doesn't edit are kept,
+    // and leave a file that no longer parses alone rather than replace it
+    let mut settings = match api::try_load_settings() {
+        Ok(settings) => settings,
+        Err(e) => {
+            app.show_toast(format!("Not saved: {:#}", e), false);
+            return;
+        }
     };
-    let _ = api::save_settings(&settings);
+    settings.base_url = app.settings_input.clone();
+    settings.visualizer_offset = app.offset_input.parse().unwrap_or(app.visualizer_offset);
+    settings.theme = app.theme.clone();
+    settings.visualizer_style = app.viz_style.clone();
+    settings.layout = app.layout.clone();
+    settings.show_stats_tab = app.show_stats_tab;
+    if let Err(e) = api::save_settings(&settings) {
+        app.show_toast(format!("Not saved: {:#}", e), false);
+    }
 }
 
 fn handle_debug_keys(app: &mut App, key: event::KeyEvent) {
@@ -1522,19 +1821,35 @@ fn handle_menu_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mutex<App>
         }
         KeyCode::Enter => {
             if let Some(idx) = app.menu_state.selected() {
-                let item = app.menu_items[idx].trim();
-                if item.contains("Skip") { tokio::spawn(async_simple_command(app_arc.clone(), "/webhook/audio".to_string(), SimplePayload { action: "skip", guild_id: app.guild_id.clone(), user_id: app.user_id.clone() })); }
+                let item = app.menu_items[idx].trim().to_string();
+                let item = item.as_str();
+                if let Some(name) = item.strip_prefix("[>] ") {
+                    match app.aliases.get(name).cloned() {
+                        Some(Alias::Query(query)) => { tokio::spawn(async_play_track(app_arc.clone(), query)); }
+                        Some(Alias::Macro(_)) => { tokio::spawn(async_run_macro(app_arc.clone(), name.to_string())); }
+                        None => {}
+                    }
+                }
+                else if item.contains("Skip") { tokio::spawn(async_undoable_command(app_arc.clone(), "skip")); }
                 else if item.contains("Pause/Resume") { tokio::spawn(async_simple_command(app_arc.clone(), "/webhook/audio".to_string(), SimplePayload { action: "pause", guild_id: app.guild_id.clone(), user_id: app.user_id.clone() })); }
-                else if item.contains("Stop") { tokio::spawn(async_simple_command(app_arc.clone(), "/webhook/audio".to_string(), SimplePayload { action: "stop", guild_id: app.guild_id.clone(), user_id: app.user_id.clone() })); }
-                else if item.contains("Shuffle") { tokio::spawn(async_simple_command(app_arc.clone(), "/webhook/audio".to_string(), SimplePayload { action: "shuffle", guild_id: app.guild_id.clone(), user_id: app.user_id.clone() })); }
-                else if item.contains("Clear Queue") { tokio::spawn(async_simple_command(app_arc.clone(), "/webhook/audio".to_string(), SimplePayload { action: "clear", guild_id: app.guild_id.clone(), user_id: app.user_id.clone() })); }
+                else if item.contains("Stop") { app.view = View::Main; app.request_wipe("stop", app_arc.clone()); }
+                else if item.contains("Shuffle") { tokio::spawn(async_undoable_command(app_arc.clone(), "shuffle")); }
+                else if item.contains("Clear Queue") { app.view = View::Main; app.request_wipe("clear", app_arc.clone()); }
                 else if item.contains("Loop Track") { app.loop_mode = "track".to_string(); tokio::spawn(async_simple_command(app_arc.clone(), "/webhook/audio".to_string(), LoopPayload { action: "loop", guild_id: app.guild_id.clone(), user_id: app.user_id.clone(), loop_mode: "track".to_string() })); }
                 else if item.contains("Loop Queue") { app.loop_mode = "queue".to_string(); tokio::spawn(async_simple_command(app_arc.clone(), "/webhook/audio".to_string(), LoopPayload { action: "loop", guild_id: app.guild_id.clone(), user_id: app.user_id.clone(), loop_mode: "queue".to_string() })); }
                 else if item.contains("Loop Off") { app.loop_mode = "off".to_string(); tokio::spawn(async_simple_command(app_arc.clone(), "/webhook/audio".to_string(), LoopPayload { action: "loop", guild_id: app.guild_id.clone(), user_id: app.user_id.clone(), loop_mode: "off".to_string() })); }
                 else if item.contains("24/7 Mode") { tokio::spawn(async_simple_command(app_arc.clone(), "/webhook/audio".to_string(), TwentyFourSevenPayload { action: "247", guild_id: app.guild_id.clone(), user_id: app.user_id.clone(), enabled: None })); }
-                else if item.contains("Filters...") { app.view = View::FilterMenu; }
+                else if item.contains("Filters...") {
+                    app.filter_items = filters::load_presets().0;
+                    app.filter_state.select(Some(0));
+                    app.view = View::FilterMenu;
+                }
+                else if item.contains("Favorites...") {
+                    app.favorite_items = favorites::load(&app.profile);
+                    app.favorites_state.select(if app.favorite_items.is_empty() { None } else { Some(0) });
+                    app.view = View::Favorites;
+                }
                 else if item.contains("Lyrics") { tokio::spawn(async_fetch_lyrics(app_arc.clone())); }
-                else if item.contains("Play Turip") { tokio::spawn(async_play_track(app_arc.clone(), "https://open.spotify.com/track/2RQWB4Asy1rjZL4IUcJ7kn".to_string())); }
                 else if item.contains("Auth") { app.view = View::AuthMenu; }
                 else if item.contains("Settings") { 
                     app.settings_input = app.base_url.clone();
@@ -1542,7 +1857,7 @@ fn handle_menu_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mutex<App>
                 }
                 else if item.contains("Exit TUI") { return Ok(true); }
 
-                if !item.contains("Filters...") && !item.contains("Lyrics") && !item.contains("Auth") && !item.contains("Settings") {
+                if app.view == View::Menu && !item.contains("Filters...") && !item.contains("Favorites...") && !item.contains("Lyrics") && !item.contains("Auth") && !item.contains("Settings") {
                     app.view = View::Main;
                 }
             }
@@ -1570,10 +1885,17 @@ fn handle_filter_menu_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mut
             };
             app.filter_state.select(Some(i));
         }
-        KeyCode::Enter => {
-            if let Some(idx) = app.filter_state.selected() {
-                let style = app.filter_items[idx];
-                let filters = get_filters_for_style(style);
+        KeyCode::Enter | KeyCode::Char('a') | KeyCode::Char('ф') => {
+            if let Some(preset) = app.filter_state.selected().and_then(|idx| app.filter_items.get(idx)) {
+                // Enter replaces the active filters, 'a' stacks the preset on top of them
+                let filters = if key.code == KeyCode::Enter {
+                    preset.filters.clone()
+                } else {
+                    let mut stacked = app.active_filters.clone();
+                    filters::merge(&mut stacked, &preset.filters);
+                    stacked
+                };
+                app.active_filters = filters.clone();
                 let payload = FilterPayload {
                     action: "filter",
                     guild_id: app.guild_id.clone(),
@@ -1588,6 +1910,72 @@ fn handle_filter_menu_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mut
     }
 }
 
+fn handle_favorites_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mutex<App>>) {
+    match key.code {
+        KeyCode::Esc => app.view = View::Main,
+        KeyCode::Backspace => app.view = View::Menu,
+        KeyCode::Down | KeyCode::Char('j') | KeyCode::Char('о') if !app.favorite_items.is_empty() => {
+            let i = match app.favorites_state.selected() {
+                Some(i) => if i >= app.favorite_items.len() - 1 { 0 } else { i + 1 },
+                None => 0,
+            };
+            app.favorites_state.select(Some(i));
+        }
+        KeyCode::Up | KeyCode::Char('k') | KeyCode::Char('л') if !app.favorite_items.is_empty() => {
+            let i = match app.favorites_state.selected() {
+                Some(i) => if i == 0 { app.favorite_items.len() - 1 } else { i - 1 },
+                None => 0,
+            };
+            app.favorites_state.select(Some(i));
+        }
+        KeyCode::Enter => {
+            if let Some(fav) = app.favorites_state.selected().and_then(|idx| app.favorite_items.get(idx)) {
+                tokio::spawn(async_play_track(app_arc, fav.query.clone()));
+                app.view = View::Main;
+            }
+        }
+        KeyCode::Char('d') | KeyCode::Char('в') | KeyCode::Delete => {
+            if let Some(name) = app.favorites_state.selected().and_then(|idx| app.favorite_items.get(idx)).map(|f| f.name.clone()) {
+                match favorites::remove(&app.profile, &name) {
+                    Ok(_) => app.log(format!("Removed favorite '{}'", name)),
+                    Err(e) => app.log(format!("Failed to remove favorite: {}", e)),
+                }
+                app.favorite_items = favorites::load(&app.profile);
+                let len = app.favorite_items.len();
+                app.favorites_state.select(if len == 0 { None } else { app.favorites_state.selected().map(|i| i.min(len - 1)) });
+            }
+        }
+        _ => {}
+    }
+}
+
+fn handle_search_results_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mutex<App>>) {
+    match key.code {
+        KeyCode::Esc | KeyCode::Backspace => app.view = View::Main,
+        KeyCode::Down | KeyCode::Char('j') | KeyCode::Char('о') => {
+            let i = match app.search_state.selected() {
+                Some(i) => if i >= app.search_results.len() - 1 { 0 } else { i + 1 },
+                None => 0,
+            };
+            app.search_state.select(Some(i));
+        }
+        KeyCode::Up | KeyCode::Char('k') | KeyCode::Char('л') => {
+            let i = match app.search_state.selected() {
+                Some(i) => if i == 0 { app.search_results.len() - 1 } else { i - 1 },
+                None => 0,
+            };
+            app.search_state.select(Some(i));
+        }
+        KeyCode::Enter => {
+            if let Some(track) = app.search_state.selected().and_then(|idx| app.search_results.get(idx)) {
+                tokio::spawn(async_play_track(app_arc, track.uri.clone()));
+                app.view = View::Main;
+            }
+        }
+        _ => {}
+    }
+}
+
 fn handle_auth_menu_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mutex<App>>) {
     match key.code {
         KeyCode::Esc | KeyCode::Tab => app.view = View::Main,
@@ -1642,52 +2030,6 @@ fn handle_auth_menu_keys(app: &mut App, key: event::KeyEvent, app_arc: Arc<Mutex
     }
 }
 
-fn get_filters_for_style(style: &str) -> AudioFilters {
-    match style.to_lowercase().as_str() {
-        "clear" => AudioFilters::default(),
-        "bassboost" => AudioFilters {
-            equalizer: Some(vec![
-                EqualizerBand { band: 0, gain: 0.2 },
-                EqualizerBand { band: 1, gain: 0.15 },
-                EqualizerBand { band: 2, gain: 0.1 },
-                EqualizerBand { band: 3, gain: 0.05 },
-                EqualizerBand { band: 4, gain: 0.0 },
-                EqualizerBand { band: 5, gain: -0.05 },
-            ]),
-            ..Default::default()
-        },
-        "soft" => AudioFilters {
-            low_pass: Some(LowPassOptions { smoothing: Some(20.0) }),
-            ..Default::default()
-        },
-        "nightcore" => AudioFilters {
-            timescale: Some(TimescaleOptions { speed: Some(1.1), pitch: Some(1.1), rate: Some(1.0) }),
-            ..Default::default()
-        },
-        "vaporwave" => AudioFilters {
-            timescale: Some(TimescaleOptions { speed: Some(0.85), pitch: Some(0.8), rate: Some(1.0) }),
-            ..Default::default()
-        },
-        "8d" => AudioFilters {
-            rotation: Some(RotationOptions { rotation_hz: Some(0.2) }),
-            ..Default::default()
-        },
-        "tremolo" => AudioFilters {
-            tremolo: Some(TremoloOptions { frequency: Some(2.0), depth: Some(0.5) }),
-            ..Default::default()
-        },
-        "vibrato" => AudioFilters {
-            vibrato: Some(VibratoOptions { frequency: Some(2.0), depth: Some(0.5) }),
-            ..Default::default()
-        },
-        "karaoke" => AudioFilters {
-            karaoke: Some(KaraokeOptions { level: Some(1.0), mono_level: Some(1.0), filter_band: Some(220.0), filter_width: Some(100.0) }),
-            ..Default::default()
-        },
-        _ => AudioFilters::default(),
-    }
-}
-
 fn ui(f: &mut Frame, app: &mut App) {
     let theme = get_theme(&app.theme);
     
@@ -1822,12 +2164,16 @@ fn ui(f: &mut Frame, app: &mut App) {
     let status_bar_area = main_layout[2];
 
     // Render Tabs
-    let tab_titles = vec![" [1] PLAYER ", " [2] LYRICS ", " [3] SETTINGS ", " [4] DEBUG "];
+    let mut tab_titles = vec![" [1] PLAYER ", " [2] LYRICS ", " [3] SETTINGS ", " [4] DEBUG "];
+    if app.show_stats_tab {
+        tab_titles.push(" [5] STATS ");
+    }
     let selected_tab = match app.view {
-        View::Main | View::Menu | View::FilterMenu | View::AuthMenu | View::AuthResult => 0,
+        View::Main | View::Menu | View::FilterMenu | View::Favorites | View::SearchResults | View::Confirm | View::AuthMenu | View::AuthResult => 0,
         View::Lyrics => 1,
         View::Settings => 2,
         View::Debug => 3,
+        View::Stats => 4,
         _ => 0,
     };
 
@@ -1901,6 +2247,10 @@ fn ui(f: &mut Frame, app: &mut App) {
                     Span::styled(h_l(SettingsField::Layout, "UI Layout: "), h_s(SettingsField::Layout)),
                     Span::styled(format!("< {} >", app.layout), h_s(SettingsField::Layout)),
                 ]),
+                Line::from(vec![
+                    Span::styled(h_l(SettingsField::StatsTab, "Stats Tab: "), h_s(SettingsField::StatsTab)),
+                    Span::styled(format!("< {} >", if app.show_stats_tab { "On" } else { "Off" }), h_s(SettingsField::StatsTab)),
+                ]),
                 Line::from(""),
                 Line::from(if is_ed {
                     Span::styled("TYPE TO EDIT, ENTER TO FINISH", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
@@ -1939,6 +2289,29 @@ fn ui(f: &mut Frame, app: &mut App) {
                 }
             }
         }
+        View::Stats => {
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Thick)
+                .title(" Listening Stats (last 7 days) ")
+                .title_bottom(Line::from(" r: refresh ").centered())
+                .title_alignment(Alignment::Center)
+                .border_style(Style::default().fg(theme.primary));
+
+            let lines: Vec<Line> = app.stats_lines.iter()
+                .map(|(heading, text)| if *heading {
+                    Line::from(Span::styled(text.as_str(), Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD)))
+                } else {
+                    Line::from(Span::styled(text.as_str(), Style::default().fg(theme.text_secondary)))
+                })
+                .collect();
+
+            let p = Paragraph::new(lines)
+                .block(block)
+                .wrap(Wrap { trim: false });
+
+            f.render_widget(p, top_section);
+        }
         View::Debug => {
             let ws_status = if app.ws_connected {
                 Span::styled(" CONNECTED ", Style::default().bg(Color::Green).fg(Color::Black).add_modifier(Modifier::BOLD))
@@ -1982,6 +2355,7 @@ fn ui(f: &mut Frame, app: &mut App) {
             ("S", "SKIP"),
             ("W", "STOP"),
             ("L", "LOOP"),
+            ("*", "STAR"),
             ("R", "RELOAD"),
             ("I", "INFO"),
             ("Q", "QUIT"),
@@ -2057,7 +2431,7 @@ fn ui(f: &mut Frame, app: &mut App) {
         
         let items: Vec<ListItem> = app.menu_items
             .iter()
-            .map(|i| ListItem::new(format!("  {}  ", *i)))
+            .map(|i| ListItem::new(format!("  {}  ", i)))
             .collect();
             
         let list = List::new(items)
@@ -2085,11 +2459,19 @@ fn ui(f: &mut Frame, app: &mut App) {
             .border_type(BorderType::Thick)
             .title(format!(" Select Filter {} ", loading_text))
             .title_alignment(Alignment::Center)
+            .title_bottom(Line::from(" Enter: replace | a: stack ").centered())
             .border_style(Style::default().fg(theme.primary));
         
         let items: Vec<ListItem> = app.filter_items
             .iter()
-            .map(|i| ListItem::new(format!("  {}  ", *i)))
+            .map(|p| {
+                let mut spans = vec![Span::raw(format!("  {}  ", p.name))];
+                if !p.is_builtin() {
+                    let label = p.description.as_deref().unwrap_or("user preset");
+                    spans.push(Span::styled(label.to_string(), Style::default().fg(theme.text_secondary)));
+                }
+                ListItem::new(Line::from(spans))
+            })
             .collect();
             
         let list = List::new(items)
@@ -2100,6 +2482,138 @@ fn ui(f: &mut Frame, app: &mut App) {
         f.render_stateful_widget(list, area, &mut app.filter_state);
     }
 
+    if app.view == View::Favorites {
+        let area = centered_rect(60, 60, f.area());
+
+        // Shadow
+        let shadow_area = Rect { x: area.x + 1, y: area.y + 1, width: area.width, height: area.height };
+        if shadow_area.right() < f.area().right() && shadow_area.bottom() < f.area().bottom() {
+            f.render_widget(Block::default().bg(Color::Rgb(10, 10, 20)), shadow_area);
+        }
+
+        f.render_widget(Clear, area);
+
+        let fav_block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Thick)
+            .title(format!(" Favorites ({}) ", app.profile))
+            .title_alignment(Alignment::Center)
+            .title_bottom(Line::from(" Enter: enqueue | d: remove | Esc: close ").centered())
+            .border_style(Style::default().fg(theme.primary));
+
+        if app.favorite_items.is_empty() {
+            let p = Paragraph::new("No favorites yet. Press '*' while a track plays to star it.")
+                .style(Style::default().fg(theme.text_secondary))
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true })
+                .block(fav_block);
+            f.render_widget(p, area);
+        } else {
+            let items: Vec<ListItem> = app.favorite_items
+                .iter()
+                .map(|fav| {
+                    let mut spans = vec![
+                        Span::raw(format!("  {}  ", fav.name)),
+                        Span::styled(fav.display_title(), Style::default().fg(theme.text_secondary)),
+                    ];
+                    if !fav.tags.is_empty() {
+                        spans.push(Span::styled(format!("  [{}]", fav.tags.join(", ")), Style::default().fg(theme.highlight)));
+                    }
+                    ListItem::new(Line::from(spans))
+                })
+                .collect();
+
+            let list = List::new(items)
+                .block(fav_block)
+                .highlight_style(Style::default().bg(theme.primary).fg(Color::Black).add_modifier(Modifier::BOLD))
+                .highlight_symbol(" >> ");
+
+            f.render_stateful_widget(list, area, &mut app.favorites_state);
+        }
+    }
+
+    if app.view == View::Confirm {
+        let area = centered_rect(50, 30, f.area());
+
+        // Shadow
+        let shadow_area = Rect { x: area.x + 1, y: area.y + 1, width: area.width, height: area.height };
+        if shadow_area.right() < f.area().right() && shadow_area.bottom() < f.area().bottom() {
+            f.render_widget(Block::default().bg(Color::Rgb(10, 10, 20)), shadow_area);
+        }
+
+        f.render_widget(Clear, area);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Thick)
+            .title(" ⚠ Confirm ")
+            .title_alignment(Alignment::Center)
+            .border_style(Style::default().fg(Color::Yellow));
+
+        let question = match app.pending_confirm {
+            Some("stop") => format!("Stop playback and drop {} queued tracks?", app.queue_total),
+            _ => format!("Clear {} queued tracks?", app.queue_total),
+        };
+        let text = vec![
+            Line::from(""),
+            Line::from(Span::styled(question, Style::default().add_modifier(Modifier::BOLD))),
+            Line::from(""),
+            Line::from(vec![
+                Span::raw("Press "),
+                Span::styled(" y ", Style::default().bg(Color::Red).fg(Color::White).add_modifier(Modifier::BOLD)),
+                Span::raw(" to confirm, "),
+                Span::styled(" n ", Style::default().bg(theme.primary).fg(Color::Black).add_modifier(Modifier::BOLD)),
+                Span::raw(" to cancel"),
+            ]),
+        ];
+
+        let p = Paragraph::new(text)
+            .alignment(Alignment::Center)
+            .block(block)
+            .wrap(Wrap { trim: true });
+
+        f.render_widget(p, area);
+    }
+
+    if app.view == View::SearchResults {
+        let area = centered_rect(60, 60, f.area());
+
+        // Shadow
+        let shadow_area = Rect { x: area.x + 1, y: area.y + 1, width: area.width, height: area.height };
+        if shadow_area.right() < f.area().right() && shadow_area.bottom() < f.area().bottom() {
+            f.render_widget(Block::default().bg(Color::Rgb(10, 10, 20)), shadow_area);
+        }
+
+        f.render_widget(Clear, area);
+
+        let loading_text = if app.is_loading { " ⏳ " } else { "" };
+        let results_block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Thick)
+            .title(format!(" Search Results {} ", loading_text))
+            .title_alignment(Alignment::Center)
+            .title_bottom(Line::from(" Enter: enqueue | Esc: cancel ").centered())
+            .border_style(Style::default().fg(theme.primary));
+
+        let items: Vec<ListItem> = app.search_results
+            .iter()
+            .map(|t| {
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("  {}  ", t.title)),
+                    Span::styled(format!("{} ", t.author), Style::default().fg(theme.text_secondary)),
+                    Span::styled(format!("[{}]", search::format_duration(t.duration_ms)), Style::default().fg(theme.text_secondary)),
+                ]))
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(results_block)
+            .highlight_style(Style::default().bg(theme.primary).fg(Color::Black).add_modifier(Modifier::BOLD))
+            .highlight_symbol(" >> ");
+
+        f.render_stateful_widget(list, area, &mut app.search_state);
+    }
+
     if app.view == View::AuthMenu {
         let area = centered_rect(40, 40, f.area());
         
@@ -2191,6 +2705,26 @@ fn ui(f: &mut Frame, app: &mut App) {
         f.render_widget(p, area);
     }
 
+    if let Some(toast) = app.active_toast() {
+        let screen = f.area();
+        let width = (toast.message.chars().count() as u16 + 4).min(screen.width);
+        let area = Rect {
+            x: screen.right().saturating_sub(width + 1),
+            y: screen.bottom().saturating_sub(5),
+            width,
+            height: 3.min(screen.height),
+        };
+        f.render_widget(Clear, area);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(theme.primary));
+        let p = Paragraph::new(toast.message.as_str())
+            .block(block)
+            .alignment(Alignment::Center);
+        f.render_widget(p, area);
+    }
+
     if let Some(msg) = &app.fatal_error {
         let area = centered_rect(60, 25, f.area());
         f.render_widget(Clear, area);
@@ -2330,7 +2864,7 @@ fn render_now_playing(f: &mut Frame, app: &mut App, theme: &Theme, area: Rect) {
         .borders(Borders::ALL)
         .border_type(BorderType::Thick)
         .border_style(Style::default().fg(theme.border))
-        .title(" Now Playing ")
+        .title(if app.is_current_favorite() { " Now Playing ★ " } else { " Now Playing " })
         .title_style(Style::default().fg(theme.primary).add_modifier(Modifier::BOLD));
 
     if let Some(current) = &app.current_track {
@@ -2386,7 +2920,16 @@ fn render_now_playing(f: &mut Frame, app: &mut App, theme: &Theme, area: Rect) {
 fn render_queue(f: &mut Frame, app: &mut App, theme: &Theme, area: Rect) {
     let loop_status = app.loop_mode.to_uppercase();
     let loading_indicator = if app.is_loading { " [L] " } else { " " };
-    let title = format!(" Queue ({}){} ", loop_status, loading_indicator);
+    let mut title_spans = vec![Span::raw(format!(" Queue ({}) ", loop_status))];
+    for kind in filters::active_kinds(&app.active_filters) {
+        title_spans.push(Span::styled(
+            format!("[{}]", kind.label()),
+            Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD),
+        ));
+        title_spans.push(Span::raw(" "));
+    }
+    title_spans.push(Span::raw(loading_indicator.trim_start()));
+    let title = Line::from(title_spans);
     
     let content_block = Block::default()
         .borders(Borders::ALL)
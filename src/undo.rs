@@ -0,0 +1,251 @@
+//! Single-level undo for `stop`, `clear`, `skip` and `shuffle`.
+//!
+//! Before one of those actions the whole queue is snapshotted to `undo.json`
+//! under the data dir. Restoring clears the queue, re-enqueues the snapshot in
+//! its original order and, for `skip`/`stop`, starts the interrupted track
+//! again and seeks back to where it was.
+
+use crate::api::{
+    self, PlayPayload, SeekPayload, SimplePayload, fetch_audio, load_auth, undo_file_path,
+};
+use crate::queue::{self, QueueEntry};
+use anyhow::{Context, Result, bail};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Snapshot {
+    /// The action the snapshot was taken for
+    pub action: String,
+    /// Unix timestamp in seconds
+    pub at: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guild_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current: Option<QueueEntry>,
+    /// Playback position of `current`
+    #[serde(default)]
+    pub position_ms: u64,
+    #[serde(default)]
+    pub upcoming: Vec<QueueEntry>,
+}
+
+/// Snapshot the queue before `action`; [`Snapshot::save`] it once the action
+/// went through, so a failed action leaves the previous undo in place.
+/// `None` under `--dry-run`.
+pub async fn take(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    action: &str,
+    guild_id: Option<String>,
+    user_id: Option<String>,
+) -> Result<Option<Snapshot>> {
+    if api::dry_run().is_some() {
+        return Ok(None);
+    }
+    let (current, upcoming) =
+        queue::fetch_split(client, base_url, token, guild_id.clone(), user_id.clone()).await?;
+    let position_ms = if current.is_some() {
+        let payload = SimplePayload {
+            action: "nowplaying",
+            guild_id: guild_id.clone(),
+            user_id,
+        };
+        fetch_audio(client, base_url, token, &payload)
+            .await
+            .ok()
+            .and_then(|json| {
+                let np = json.get("now_playing").cloned().unwrap_or(json);
+                ["elapsedMs", "elapsed_ms", "position"]
+                    .iter()
+                    .find_map(|k| np.get(*k).and_then(|v| v.as_u64()))
+            })
+            .unwrap_or(0)
+    } else {
+        0
+    };
+    Ok(Some(Snapshot {
+        action: action.to_string(),
+        at: chrono::Local::now().timestamp(),
+        guild_id,
+        current,
+        position_ms,
+        upcoming,
+    }))
+}
+
+impl Snapshot {
+    pub fn save(&self) -> Result<()> {
+        let path = undo_file_path().context("could not determine data dir")?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?).context("writing undo snapshot")
+    }
+}
+
+/// What [`restore`] sends for a snapshot, in order.
+#[derive(Debug)]
+struct Plan {
+    /// Clear the queue first (after `stop` it is already empty)
+    clear: bool,
+    /// Re-enqueued one at a time
+    tracks: Vec<QueueEntry>,
+    /// Skip the track that replaced the restored current one
+    skip: bool,
+    /// Seek the restored current track back to here
+    seek_ms: Option<u64>,
+}
+
+impl Snapshot {
+    fn plan(&self) -> Plan {
+        // `skip` and `stop` interrupted the current track, so it goes back in
+        // front; `clear` and `shuffle` left it playing.
+        let current = self
+            .current
+            .clone()
+            .filter(|_| matches!(self.action.as_str(), "skip" | "stop"));
+        let restarts = current.is_some();
+        Plan {
+            clear: self.action != "stop",
+            tracks: current.into_iter().chain(self.upcoming.iter().cloned()).collect(),
+            // The track that replaced the skipped one was re-enqueued after it
+            skip: restarts && self.action == "skip",
+            seek_ms: (restarts && self.position_ms > 0).then_some(self.position_ms),
+        }
+    }
+}
+
+pub fn load() -> Option<Snapshot> {
+    let content = fs::read_to_string(undo_file_path()?).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Restore the last snapshot and forget it. Returns a summary line.
+pub async fn restore(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    channel_id: Option<String>,
+    user_id: Option<String>,
+) -> Result<String> {
+    let Some(snapshot) = load() else {
+        bail!("nothing to undo");
+    };
+    let guild_id = snapshot.guild_id.clone();
+    let simple = |action: &'static str| SimplePayload {
+        action,
+        guild_id: guild_id.clone(),
+        user_id: user_id.clone(),
+    };
+    let plan = snapshot.plan();
+    if plan.clear {
+        fetch_audio(client, base_url, token, &simple("clear")).await?;
+    }
+
+    let saved = load_auth();
+    let tracks = &plan.tracks;
+    let mut restored = 0;
+    for entry in tracks {
+        let payload = PlayPayload {
+            action: "play",
+            guild_id: guild_id.clone(),
+            channel_id: channel_id.clone(),
+            query: entry.uri.clone(),
+            user_id: user_id.clone(),
+            requested_by: entry
+                .requested_by
+                .clone()
+                .or_else(|| saved.as_ref().and_then(|a| a.username.clone())),
+            avatar_url: saved.as_ref().and_then(|a| a.avatar_url.clone()),
+            position: None,
+        };
+        // One at a time so the tracks keep their order
+        if fetch_audio(client, base_url, token, &payload).await.is_ok() {
+            restored += 1;
+        }
+    }
+    if restored == 0 && !tracks.is_empty() {
+        bail!("no tracks could be re-enqueued");
+    }
+
+    if plan.skip {
+        fetch_audio(client, base_url, token, &simple("skip")).await?;
+    }
+    if let Some(position_ms) = plan.seek_ms {
+        // Give the player a moment to load the track before seeking
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let seek = SeekPayload {
+            action: "seek",
+            guild_id: guild_id.clone(),
+            user_id: user_id.clone(),
+            position_ms,
+        };
+        fetch_audio(client, base_url, token, &seek).await?;
+    }
+
+    if api::dry_run().is_none()
+        && let Some(path) = undo_file_path()
+    {
+        let _ = fs::remove_file(path);
+    }
+    Ok(format!(
+        "Restored {} of {} tracks (undid {})",
+        restored,
+        tracks.len(),
+        snapshot.action
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn snapshot(action: &str, position_ms: u64) -> Snapshot {
+        serde_json::from_value(json!({
+            "action": action,
+            "at": 0,
+            "current": {"uri": "now"},
+            "position_ms": position_ms,
+            "upcoming": [{"uri": "next"}, {"uri": "later"}],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn plans_each_restore() {
+        let cases = [
+            ("skip", 30_000, true, ["now", "next", "later"].as_slice(), true, Some(30_000)),
+            ("stop", 30_000, false, &["now", "next", "later"], false, Some(30_000)),
+            ("stop", 0, false, &["now", "next", "later"], false, None),
+            // the current track kept playing
+            ("clear", 30_000, true, &["next", "later"], false, None),
+            ("shuffle", 30_000, true, &["next", "later"], false, None),
+        ];
+        for (action, position_ms, clear, tracks, skip, seek_ms) in cases {
+            let plan = snapshot(action, position_ms).plan();
+            let uris: Vec<&str> = plan.tracks.iter().map(|t| t.uri.as_str()).collect();
+            assert_eq!(
+                (plan.clear, uris.as_slice(), plan.skip, plan.seek_ms),
+                (clear, tracks, skip, seek_ms),
+                "{}",
+                action
+            );
+        }
+    }
+
+    #[test]
+    fn plans_a_skip_with_nothing_playing() {
+        let snapshot = Snapshot {
+            current: None,
+            ..snapshot("skip", 30_000)
+        };
+        let plan = snapshot.plan();
+        assert!(!plan.skip && plan.seek_ms.is_none(), "{:?}", plan);
+        assert_eq!(plan.tracks.len(), 2);
+    }
+}
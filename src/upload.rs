@@ -0,0 +1,263 @@
+//! Uploading local audio files so they can be enqueued like any other track.
+//!
+//! `play ./track.flac` streams the file to `/webhook/upload` as multipart form
+//! data and enqueues the URI the server hands back. Title and artist are read
+//! from the file's tags for display and sent along as form fields.
+
+use crate::api::{self, build_url, read_response};
+use anyhow::{Context, Result, bail};
+use colored::Colorize;
+use futures_util::TryStreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::multipart::{Form, Part};
+use reqwest::{Body, Client};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use symphonia::core::probe::Hint;
+use tokio_util::io::ReaderStream;
+
+/// Largest file the CLI will try to upload.
+pub const MAX_UPLOAD_BYTES: u64 = 100 * 1024 * 1024;
+
+/// The shared client times out after 10s, far too short for an upload.
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Extensions accepted for upload and their MIME types.
+const AUDIO_TYPES: &[(&str, &str)] = &[
+    ("mp3", "audio/mpeg"),
+    ("flac", "audio/flac"),
+    ("ogg", "audio/ogg"),
+    ("opus", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("m4a", "audio/mp4"),
+    ("aac", "audio/aac"),
+    ("webm", "audio/webm"),
+];
+
+#[derive(Default, Debug)]
+pub struct TrackTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration_ms: Option<u64>,
+}
+
+impl TrackTags {
+    fn apply(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            let slot = match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => &mut self.title,
+                Some(StandardTagKey::Artist) => &mut self.artist,
+                Some(StandardTagKey::Album) => &mut self.album,
+                _ => continue,
+            };
+            // RIFF INFO strings keep their NUL terminator
+            let value = tag.value.to_string();
+            let value = value.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+            if slot.is_none() && !value.is_empty() {
+                *slot = Some(value.to_string());
+            }
+        }
+    }
+}
+
+/// Treat a play query as a local file when it is spelled like a path (`./`,
+/// `../`, `/`, `~/`) or names an existing audio file.
+pub fn local_path(query: &str) -> Option<PathBuf> {
+    let query = query.trim();
+    if query.contains("://") {
+        return None;
+    }
+    let path = match query.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()?.join(rest),
+        None => PathBuf::from(query),
+    };
+    let looks_like_path = ["./", "../", "/", "~/"]
+        .iter()
+        .any(|p| query.starts_with(p));
+    (looks_like_path || (path.is_file() && mime_type(&path).is_some())).then_some(path)
+}
+
+/// Read title, artist, album and duration from the file's tags. Unreadable
+/// files just yield empty tags.
+pub fn read_tags(path: &Path) -> TrackTags {
+    let mut tags = TrackTags::default();
+    let Ok(file) = std::fs::File::open(path) else {
+        return tags;
+    };
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let Ok(mut probed) = symphonia::default::get_probe().format(
+        &hint,
+        stream,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    ) else {
+        return tags;
+    };
+    // ID3v2 tags in front of the container end up in the probe metadata,
+    // container-native tags (Vorbis comments, MP4 atoms) in the format's.
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+        tags.apply(revision);
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        tags.apply(revision);
+    }
+    tags.duration_ms = probed.format.default_track().and_then(|track| {
+        let frames = track.codec_params.n_frames?;
+        let rate = track.codec_params.sample_rate? as u64;
+        (rate > 0).then(|| frames * 1000 / rate)
+    });
+    tags
+}
+
+fn mime_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    AUDIO_TYPES
+        .iter()
+        .find(|(e, _)| *e == ext)
+        .map(|(_, mime)| *mime)
+}
+
+/// Upload `path` and return the URI to enqueue.
+pub async fn upload(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    path: &Path,
+) -> Result<String> {
+    if !path.is_file() {
+        bail!("no such file: {}", path.display());
+    }
+    let mime = mime_type(path).with_context(|| {
+        let supported: Vec<&str> = AUDIO_TYPES.iter().map(|(e, _)| *e).collect();
+        format!(
+            "unsupported file type: {} (supported: {})",
+            path.display(),
+            supported.join(", ")
+        )
+    })?;
+    let file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("opening {}", path.display()))?;
+    let size = file.metadata().await?.len();
+    if size == 0 {
+        bail!("{} is empty", path.display());
+    }
+    if size > MAX_UPLOAD_BYTES {
+        bail!(
+            "{} is too large ({} MiB, limit is {} MiB)",
+            path.display(),
+            size / (1024 * 1024),
+            MAX_UPLOAD_BYTES / (1024 * 1024)
+        );
+    }
+
+    let tags = read_tags(path);
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "upload".to_string());
+    let display = match (&tags.title, &tags.artist) {
+        (Some(t), Some(a)) => format!("{} by {}", t, a),
+        (Some(t), None) => t.clone(),
+        _ => file_name.clone(),
+    };
+    println!(
+        "{} Uploading {}{}",
+        "⬆️".cyan(),
+        display.bold(),
+        tags.duration_ms
+            .map(|ms| format!(" [{}]", crate::search::format_duration(ms)))
+            .unwrap_or_default()
+    );
+
+    let url = build_url(base_url, "/webhook/upload");
+    if let Some(mode) = api::dry_run() {
+        let file_arg = format!("file=@{}", path.display());
+        match mode {
+            api::DryRun::Json => println!("POST {} (multipart/form-data, {})", url, file_arg),
+            api::DryRun::Curl => println!(
+                "curl -X POST {} -H \"Authorization: Bearer $JORIK_TOKEN\" -F {}",
+                shlex::try_quote(&url).unwrap_or_default(),
+                shlex::try_quote(&file_arg).unwrap_or_default()
+            ),
+        }
+        return Ok(format!("<uploaded {}>", file_name));
+    }
+
+    let pb = ProgressBar::new(size);
+    pb.set_style(
+        ProgressStyle::with_template(
+            "{spinner:.cyan} [{bar:30.cyan/blue}] {bytes}/{total_bytes} {bytes_per_sec}",
+        )
+        .unwrap_or_else(|_| ProgressStyle::default_bar())
+        .progress_chars("=> "),
+    );
+    let progress = pb.clone();
+    let stream = ReaderStream::new(file).inspect_ok(move |chunk| progress.inc(chunk.len() as u64));
+    let part = Part::stream_with_length(Body::wrap_stream(stream), size)
+        .file_name(file_name)
+        .mime_str(mime)?;
+    let mut form = Form::new().part("file", part);
+    for (field, value) in [
+        ("title", &tags.title),
+        ("artist", &tags.artist),
+        ("album", &tags.album),
+    ] {
+        if let Some(value) = value {
+            form = form.text(field, value.clone());
+        }
+    }
+
+    let mut req = client.post(&url).multipart(form).timeout(UPLOAD_TIMEOUT);
+    if let Some(bearer) = token {
+        req = req.bearer_auth(bearer);
+    }
+    let resp = req.send().await;
+    pb.finish_and_clear();
+    let resp = resp.with_context(|| format!("POST {url}"))?;
+    let json = read_response(resp, &url).await?;
+    let data = json.get("data").unwrap_or(&json);
+    ["uri", "url"]
+        .iter()
+        .find_map(|k| data.get(*k).and_then(|v| v.as_str()))
+        .map(str::to_string)
+        .context("upload response did not contain a URI")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_local_paths() {
+        let cases = [
+            ("./track.flac", Some(PathBuf::from("./track.flac"))),
+            ("../music/a b.mp3", Some(PathBuf::from("../music/a b.mp3"))),
+            ("/srv/music/track.ogg", Some(PathBuf::from("/srv/music/track.ogg"))),
+            // neither spelled like a path nor an existing file
+            ("track.flac", None),
+            ("lofi beats", None),
+            ("https://example.com/track.mp3", None),
+            ("file:///srv/music/track.ogg", None),
+        ];
+        for (query, expected) in cases {
+            assert_eq!(local_path(query), expected, "{}", query);
+        }
+        assert_eq!(local_path("~/a.mp3"), dirs::home_dir().map(|h| h.join("a.mp3")));
+    }
+
+    #[test]
+    fn knows_audio_types() {
+        assert_eq!(mime_type(Path::new("a.MP3")), Some("audio/mpeg"));
+        assert_eq!(mime_type(Path::new("a.txt")), None);
+        assert_eq!(mime_type(Path::new("mp3")), None);
+    }
+}
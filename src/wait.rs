@@ -0,0 +1,311 @@
+//! `jorik wait`: block until something happens in playback, for scripts like
+//! `jorik play intro && jorik wait track-end && jorik play main-set`.
+
+use crate::api::{self, PlaybackState, QueuePayload, SimplePayload, WsEvent, fetch_audio};
+use crate::ws::{self, Subscription, Update};
+use anyhow::{Context, Result, bail};
+use clap::{Args, ValueEnum};
+use reqwest::Client;
+use serde_json::Value;
+use std::time::Duration;
+
+/// Exit code when `--timeout` runs out, the same as coreutils `timeout`.
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Condition {
+    /// The current track finishes
+    TrackEnd,
+    /// A new track starts
+    TrackStart,
+    /// Nothing is playing and nothing is queued
+    QueueEmpty,
+    /// A track is playing and not paused
+    Playing,
+    /// Playback is paused
+    Paused,
+}
+
+#[derive(Args, Debug)]
+pub struct WaitArgs {
+    #[arg(value_enum)]
+    pub condition: Condition,
+    /// Give up after this long (e.g. 30s, 10m)
+    #[arg(long, value_parser = humantime::parse_duration)]
+    pub timeout: Option<Duration>,
+    #[arg(long)]
+    pub guild_id: Option<String>,
+    #[arg(long)]
+    pub user_id: Option<String>,
+}
+
+impl Condition {
+    pub fn name(self) -> String {
+        self.to_possible_value()
+            .map(|v| v.get_name().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Conditions that describe a state, which may already hold, rather
+    /// than an event that has to happen.
+    fn is_state(self) -> bool {
+        matches!(self, Condition::QueueEmpty | Condition::Playing | Condition::Paused)
+    }
+}
+
+/// Wait for `condition`. Returns `false` if `timeout` ran out first.
+pub async fn run(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    args: WaitArgs,
+) -> Result<bool> {
+    let mut subscription = None;
+    run_with(client, base_url, token, args, &mut subscription).await
+}
+
+/// Like [`run`], reusing `subscription` if it is for the same guild, or
+/// leaving a new one there for the next wait.
+pub async fn run_with(
+    client: &Client,
+    base_url: &str,
+    token: Option<&str>,
+    args: WaitArgs,
+    subscription: &mut Option<Subscription>,
+) -> Result<bool> {
+    if api::dry_run().is_some() {
+        println!("wait {}", args.condition.name());
+        return Ok(true);
+    }
+    let token = token.context("wait needs a login; run `jorik auth login` first")?;
+    let guild_id = args
+        .guild_id
+        .or_else(|| subscription.as_ref().map(|s| s.guild_id.clone()));
+    let guild_id = match guild_id {
+        Some(id) => id,
+        None => ws::discover_guild(client, base_url, token, args.user_id.clone()).await?,
+    };
+    let subscription = match subscription {
+        Some(s) if s.guild_id == guild_id => s,
+        _ => subscription.insert(Subscription::new(base_url, token, guild_id)),
+    };
+    until(
+        client,
+        base_url,
+        token,
+        args.condition,
+        args.user_id,
+        subscription,
+        args.timeout,
+    )
+    .await
+}
+
+/// Wait for `condition` on `subscription`. Events received before the call
+/// don't count. Returns `false` if `timeout` ran out first.
+async fn until(
+    client: &Client,
+    base_url: &str,
+    token: &str,
+    condition: Condition,
+    user_id: Option<String>,
+    subscription: &mut Subscription,
+    timeout: Option<Duration>,
+) -> Result<bool> {
+    subscription.drain();
+    let wait = wait_for(client, base_url, token, condition, user_id, subscription);
+    match timeout {
+        Some(limit) => match tokio::time::timeout(limit, wait).await {
+            Ok(result) => result.map(|_| true),
+            Err(_) => Ok(false),
+        },
+        None => wait.await.map(|_| true),
+    }
+}
+
+async fn wait_for(
+    client: &Client,
+    base_url: &str,
+    token: &str,
+    condition: Condition,
+    user_id: Option<String>,
+    subscription: &mut Subscription,
+) -> Result<()> {
+    let guild_id = subscription.guild_id.clone();
+    if condition.is_state()
+        && subscription.is_connected()
+        && check_now(client, base_url, token, condition, &guild_id, user_id.clone()).await?
+    {
+        return Ok(());
+    }
+    while let Some(update) = subscription.next().await {
+        let met = match update {
+            // Checked once subscribed, so nothing can slip in between, and
+            // again after reconnecting in case it happened meanwhile.
+            Update::Connected if condition.is_state() => {
+                check_now(client, base_url, token, condition, &guild_id, user_id.clone()).await?
+            }
+            Update::Connected | Update::Disconnected(_) => false,
+            Update::Event { event, .. } => {
+                if event.guild_id.as_deref().is_some_and(|g| g != guild_id) {
+                    continue;
+                }
+                match from_event(condition, &event) {
+                    Some(met) => met,
+                    // The event changed the queue without saying how
+                    None => {
+                        check_now(client, base_url, token, condition, &guild_id, user_id.clone())
+                            .await?
+                    }
+                }
+            }
+        };
+        if met {
+            return Ok(());
+        }
+    }
+    bail!("WebSocket subscription ended")
+}
+
+/// Whether `event` satisfies `condition`; `None` when the event is relevant
+/// but the current state has to be fetched to tell.
+fn from_event(condition: Condition, event: &WsEvent) -> Option<bool> {
+    let event_type = event.event_type.as_str();
+    match condition {
+        Condition::TrackEnd => Some(event_type == "track_end"),
+        Condition::TrackStart => Some(event_type == "track_start"),
+        Condition::Playing | Condition::Paused => {
+            if condition == Condition::Playing && event_type == "track_start" {
+                return Some(true);
+            }
+            let playback = event.playback.clone().or_else(|| {
+                event
+                    .data
+                    .as_ref()
+                    .and_then(|d| d.get("playback"))
+                    .and_then(|p| serde_json::from_value::<PlaybackState>(p.clone()).ok())
+            });
+            Some(playback.is_some_and(|p| p.paused == (condition == Condition::Paused)))
+        }
+        Condition::QueueEmpty => match event_type {
+            // Ticks without the queue in them aren't worth a request
+            "state_update" | "initial_state" => {
+                Some(event.data.as_ref().and_then(queue_is_empty).unwrap_or(false))
+            }
+            "queue_update" => event.data.as_ref().and_then(queue_is_empty),
+            "track_end" | "player_update" => None,
+            _ => Some(false),
+        },
+    }
+}
+
+async fn check_now(
+    client: &Client,
+    base_url: &str,
+    token: &str,
+    condition: Condition,
+    guild_id: &str,
+    user_id: Option<String>,
+) -> Result<bool> {
+    let guild_id = Some(guild_id.to_string());
+    match condition {
+        Condition::QueueEmpty => {
+            let payload = QueuePayload {
+                action: "queue",
+                guild_id,
+                user_id,
+                limit: 1,
+                offset: 0,
+            };
+            let json = fetch_audio(client, base_url, Some(token), &payload).await?;
+            Ok(queue_is_empty(&json).unwrap_or(false))
+        }
+        Condition::Playing | Condition::Paused => {
+            let payload = SimplePayload {
+                action: "nowplaying",
+                guild_id,
+                user_id,
+            };
+            let json = fetch_audio(client, base_url, Some(token), &payload).await?;
+            let Some(np) = json.get("now_playing").filter(|np| np.is_object()) else {
+                return Ok(false);
+            };
+            let paused = np.get("paused").and_then(|v| v.as_bool()).unwrap_or(false);
+            Ok(paused == (condition == Condition::Paused))
+        }
+        Condition::TrackEnd | Condition::TrackStart => Ok(false),
+    }
+}
+
+/// `None` if `json` doesn't describe the queue at all.
+fn queue_is_empty(json: &Value) -> Option<bool> {
+    let queue = json.get("queue").unwrap_or(json);
+    if queue.get("current").is_none() && queue.get("upcoming").is_none() {
+        return None;
+    }
+    let playing = queue.get("current").is_some_and(|c| c.is_object());
+    let upcoming = queue
+        .get("total_upcoming")
+        .and_then(|v| v.as_u64())
+        .or_else(|| queue.get("upcoming").and_then(|v| v.as_array()).map(|a| a.len() as u64))
+        .unwrap_or(0);
+    Some(!playing && upcoming == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event(value: Value) -> WsEvent {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn reads_conditions_from_events() {
+        let playing = json!({"elapsedMs": 1000, "durationMs": 5000, "paused": false});
+        let paused = json!({"elapsedMs": 1000, "durationMs": 5000, "paused": true});
+        let cases = [
+            (Condition::TrackEnd, json!({"type": "track_end"}), Some(true)),
+            (Condition::TrackEnd, json!({"type": "track_start"}), Some(false)),
+            (Condition::TrackStart, json!({"type": "track_start"}), Some(true)),
+            (Condition::Playing, json!({"type": "track_start"}), Some(true)),
+            (Condition::Playing, json!({"type": "state_update", "playback": playing}), Some(true)),
+            (
+                Condition::Playing,
+                json!({"type": "state_update", "data": {"playback": paused}}),
+                Some(false),
+            ),
+            (Condition::Paused, json!({"type": "state_update", "playback": paused}), Some(true)),
+            (Condition::Paused, json!({"type": "queue_update"}), Some(false)),
+            (
+                Condition::QueueEmpty,
+                json!({"type": "queue_update", "data": {"current": null, "upcoming": []}}),
+                Some(true),
+            ),
+            (Condition::QueueEmpty, json!({"type": "queue_update"}), None),
+            (Condition::QueueEmpty, json!({"type": "track_end"}), None),
+            (Condition::QueueEmpty, json!({"type": "state_update", "data": {}}), Some(false)),
+            (Condition::QueueEmpty, json!({"type": "track_start"}), Some(false)),
+        ];
+        for (condition, value, expected) in cases {
+            assert_eq!(from_event(condition, &event(value.clone())), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn tells_an_empty_queue() {
+        let cases = [
+            (json!({}), None),
+            (json!({"current": null, "upcoming": []}), Some(true)),
+            (json!({"current": null, "total_upcoming": 0}), Some(true)),
+            (json!({"current": {"title": "a"}, "upcoming": []}), Some(false)),
+            (json!({"current": null, "upcoming": [{"title": "b"}]}), Some(false)),
+            (json!({"current": null, "upcoming": [], "total_upcoming": 3}), Some(false)),
+            (json!({"queue": {"current": null, "upcoming": []}}), Some(true)),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(queue_is_empty(&value), expected, "{}", value);
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! WebSocket connection to the bot, shared by the TUI, the CLI commands
+//! that stream updates (`--follow`, `events`) and `jorik daemon`.
+
+use crate::api::{PlaybackState, SimplePayload, WsEvent, WsSubscribe, fetch_audio};
+use crate::history;
+use anyhow::{Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use reqwest::Client;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, http::HeaderValue, protocol::Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+use url::Url;
+
+pub type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Pause between reconnect attempts.
+pub const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// What a [`subscribe`] receiver sees.
+#[derive(Debug)]
+pub enum Update {
+    Connected,
+    Disconnected(String),
+    /// A parsed event along with the message as received
+    Event { event: WsEvent, raw: Value },
+}
+
+/// `ws(s)://<host>/ws?token=...` for the API base URL.
+pub fn url(base_url: &str, token: &str) -> Result<Url> {
+    let mut url = Url::parse(base_url).with_context(|| format!("invalid base URL {base_url}"))?;
+    let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+    url.set_scheme(scheme).ok();
+    url.set_path("/ws");
+    url.query_pairs_mut().append_pair("token", token);
+    Ok(url)
+}
+
+/// The guild a `/webhook/audio` response is about, if it says.
+pub fn guild_from_response(json: &Value) -> Option<String> {
+    ["guild_id", "guildId"]
+        .iter()
+        .find_map(|k| json.get(*k).and_then(|v| v.as_str()).map(str::to_string))
+}
+
+/// Playback position of a state event, which servers put either at the top
+/// or in its data.
+pub fn playback(event: &WsEvent) -> Option<PlaybackState> {
+    event.playback.clone().or_else(|| {
+        event
+            .data
+            .as_ref()
+            .and_then(|d| d.get("playback"))
+            .and_then(|p| serde_json::from_value(p.clone()).ok())
+    })
+}
+
+/// Ask the server which guild the user is in, for subscribing without
+/// `--guild-id`.
+pub async fn discover_guild(
+    client: &Client,
+    base_url: &str,
+    token: &str,
+    user_id: Option<String>,
+) -> Result<String> {
+    let payload = SimplePayload {
+        action: "nowplaying",
+        guild_id: None,
+        user_id,
+    };
+    let json = fetch_audio(client, base_url, Some(token), &payload).await?;
+    guild_from_response(&json).context("could not determine the guild; pass --guild-id")
+}
+
+/// Connect and subscribe to `guild_id`'s events.
+pub async fn connect(base_url: &str, token: &str, guild_id: &str) -> Result<WsStream> {
+    let ws_url = url(base_url, token)?;
+    let mut request = ws_url.as_str().into_client_request()?;
+    let headers = request.headers_mut();
+    headers.insert("User-Agent", HeaderValue::from_static("jorik-cli"));
+    headers.insert("Origin", HeaderValue::from_str(base_url).unwrap_or_else(|_| HeaderValue::from_static("jorik-cli")));
+    if let Some(host) = ws_url.host_str() {
+        headers.insert("Host", HeaderValue::from_str(host).unwrap_or_else(|_| HeaderValue::from_static("localhost")));
+    }
+    headers.insert("Authorization", HeaderValue::from_str(&format!("Bearer {}", token)).unwrap_or_else(|_| HeaderValue::from_static("")));
+
+    let (mut stream, _) = connect_async(request).await?;
+    let sub = WsSubscribe {
+        event_type: "subscribe",
+        guild_id: guild_id.to_string(),
+    };
+    stream.send(Message::Text(serde_json::to_string(&sub)?.into())).await?;
+    Ok(stream)
+}
+
+/// Keep a subscription open in the background, reconnecting after errors.
+/// The task ends once the receiver is dropped.
+pub fn subscribe(base_url: String, token: String, guild_id: String) -> UnboundedReceiver<Update> {
+    subscribe_duplex(base_url, token, guild_id).1
+}
+
+/// Like [`subscribe`], also sending the messages given to the returned
+/// sender. Messages queued while disconnected are dropped rather than sent
+/// late.
+pub fn subscribe_duplex(
+    base_url: String,
+    token: String,
+    guild_id: String,
+) -> (UnboundedSender<String>, UnboundedReceiver<Update>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let (out_tx, mut out_rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while !tx.is_closed() {
+            let reason = match connect(&base_url, &token, &guild_id).await {
+                Ok(stream) => {
+                    while out_rx.try_recv().is_ok() {}
+                    if tx.send(Update::Connected).is_err() {
+                        return;
+                    }
+                    forward(stream, &tx, &mut out_rx).await
+                }
+                Err(e) => e.to_string(),
+            };
+            if tx.send(Update::Disconnected(reason)).is_err() {
+                return;
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    });
+    (out_tx, rx)
+}
+
+/// A [`subscribe`] receiver that remembers whether it is connected, so one
+/// connection can serve several waits in a row.
+pub struct Subscription {
+    pub guild_id: String,
+    updates: UnboundedReceiver<Update>,
+    connected: bool,
+}
+
+impl Subscription {
+    pub fn new(base_url: &str, token: &str, guild_id: String) -> Self {
+        Subscription {
+            updates: subscribe(base_url.to_string(), token.to_string(), guild_id.clone()),
+            guild_id,
+            connected: false,
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    pub async fn next(&mut self) -> Option<Update> {
+        let update = self.updates.recv().await?;
+        self.track(&update);
+        Some(update)
+    }
+
+    /// Forget events received so far.
+    pub fn drain(&mut self) {
+        while let Ok(update) = self.updates.try_recv() {
+            self.track(&update);
+        }
+    }
+
+    fn track(&mut self, update: &Update) {
+        match update {
+            Update::Connected => self.connected = true,
+            Update::Disconnected(_) => self.connected = false,
+            Update::Event { .. } => {}
+        }
+    }
+}
+
+/// Pass events on, and `outgoing` messages out, until the connection drops;
+/// returns why it did.
+async fn forward(
+    mut stream: WsStream,
+    tx: &UnboundedSender<Update>,
+    outgoing: &mut UnboundedReceiver<String>,
+) -> String {
+    loop {
+        tokio::select! {
+            msg = stream.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    let Ok(raw) = serde_json::from_str::<Value>(&text) else {
+                        continue;
+                    };
+                    let Ok(event) = serde_json::from_value::<WsEvent>(raw.clone()) else {
+                        continue;
+                    };
+                    // Every subscriber keeps the history, not just the TUI
+                    history::record_event(
+                        &event.event_type,
+                        event.data.as_ref(),
+                        event.guild_id.as_deref(),
+                    );
+                    if tx.send(Update::Event { event, raw }).is_err() {
+                        return "receiver closed".to_string();
+                    }
+                }
+                Some(Ok(Message::Close(_))) => return "closed by server".to_string(),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return e.to_string(),
+                None => return "connection closed".to_string(),
+            },
+            Some(text) = outgoing.recv() => {
+                if let Err(e) = stream.send(Message::Text(text.into())).await {
+                    return e.to_string();
+                }
+            }
+        }
+    }
+}